@@ -18,11 +18,11 @@ use super::{AiTarget, MoveTo};
 
 
 pub fn sample_behavior() -> BehaviorTree {
-    let locomotion = BasicLocomotion {speed: 1.0, max_acceleration: 0.4};
+    let locomotion = BasicLocomotion {speed: 1.0, max_acceleration: 24.0};
     let root = ConditionalLoop::new(ForcedSequence::new(vec![
             ConditionalLoop::new(Sequence::new(vec![
                 JumpTask::new(
-                    JumpUp { target_velocity: Vec3::Y, max_acceleration: 0.4 },
+                    JumpUp { target_velocity: Vec3::Y, max_acceleration: 24.0 },
                     0.2
                 ),
                 WaitTask::new(1.0),
@@ -1,21 +1,35 @@
 
 use std::marker::PhantomData;
 
-use bevy::{prelude::*, input::mouse::MouseMotion};
+use bevy::{
+    prelude::*,
+    input::{mouse::{MouseMotion, MouseWheel, MouseScrollUnit}, gamepad::{GamepadAxisChangedEvent, GamepadButtonChangedEvent}},
+    window::{CursorMoved, Window},
+};
 
-use super::{button::ButtonInput, CascadeInputSet};
+use super::{button::ButtonInput, CascadeInputSet, record::replaying, context::{InputContext, InputContextStack}, order::{CascadeNode, CascadeOrder}};
 
 
 pub struct AxisInputPlugin;
 impl Plugin for AxisInputPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(PreUpdate, update_mouse_mapped_sticks.in_set(CascadeInputSet::DeviceMappedInputs))
+            .add_systems(PreUpdate, (
+                update_mouse_mapped_sticks,
+                update_wheel_mapped_inputs,
+                update_gamepad_mapped_sticks,
+                update_gamepad_mapped_axes,
+            ).in_set(CascadeInputSet::DeviceMappedInputs).run_if(not(replaying)))
         ;
     }
 }
 
 
+#[derive(Debug, Component, Clone, Copy, Default, PartialEq)]
+pub struct AxisInput {
+    pub value: f32,
+}
+
 #[derive(Debug, Component, Clone, Copy, Default, PartialEq)]
 pub struct StickInput {
     pub value: Vec2,
@@ -44,12 +58,19 @@ pub struct StickButtons {
     pub negative_y: Entity,
     pub positive_y: Entity,
 }
+impl CascadeNode for StickButtons {
+    fn sources(&self) -> Vec<Entity> {
+        vec![self.negative_x, self.positive_x, self.negative_y, self.positive_y]
+    }
+}
 
 pub fn update_four_button_axis (
-    mut sticks: Query<(&mut StickInput, &StickButtons)>,
+    mut sticks: Query<(Entity, &mut StickInput, &StickButtons)>,
     buttons: Query<&ButtonInput>,
+    order: Res<CascadeOrder>,
 ) {
-    for (mut stick, src) in sticks.iter_mut() {
+    for (entity, mut stick, src) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
         let (
             Ok(negative_x), Ok(positive_x), Ok(negative_y), Ok(positive_y)
         ) = (
@@ -70,18 +91,100 @@ pub fn update_four_button_axis (
 }
 
 
-#[derive(Debug, Component, Clone, Copy, Default, PartialEq)]
+/// Mouse motion of the frame, in counts multiplied by `sensitivity`.
+///
+/// Counts sum up the same regardless of frame rate, and so do the acceleration and the smoothing,
+/// which work on the speed over time.
+#[derive(Debug, Component, Clone, PartialEq)]
 pub struct MappedMouse {
     pub sensitivity: Vec2,
+    /// Extra gain per speed in counts per second, `0.0` for none.
+    pub acceleration: f32,
+    /// Seconds for the smoothed speed to reach 63% of a change, `0.0` for none.
+    pub smoothing: f32,
+    /// Reads device motion, bypassing pointer acceleration of OS.
+    /// Cursor motion in physical pixels otherwise, falling back to device motion while the cursor is locked and does not move.
+    pub raw: bool,
+    /// Smoothed speed in counts per second.
+    speed: Vec2,
+}
+impl MappedMouse {
+    pub fn new(sensitivity: Vec2) -> Self {
+        Self {
+            sensitivity: sensitivity,
+            acceleration: 0.0,
+            smoothing: 0.0,
+            raw: true,
+            speed: Vec2::ZERO,
+        }
+    }
+
+    /// Motion of the frame after smoothing.
+    /// The speed follows the input speed exponentially, and the motion is its exact integral over the frame,
+    /// so the total motion is kept and is the same for any frame time.
+    fn smooth(&mut self, delta: Vec2, delta_seconds: f32) -> Vec2 {
+        if self.smoothing <= 0.0 || delta_seconds <= 0.0 {
+            self.speed = Vec2::ZERO;
+            return delta;
+        }
+        let input_speed = delta / delta_seconds;
+        let decay = 1.0 - (-delta_seconds / self.smoothing).exp();
+        let motion = input_speed * delta_seconds + (self.speed - input_speed) * self.smoothing * decay;
+        self.speed = self.speed.lerp(input_speed, decay);
+        motion
+    }
 }
 
+/// Whether the cursor is locked into the window, written by the app.
+/// [`MappedMouse`] drops motion while unlocked, so that the view never turns by moving outside the window.
+/// Always locked if absent.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Eq)]
+pub struct CursorLocked(pub bool);
+
 fn update_mouse_mapped_sticks(
-    mut sticks: Query<(&mut StickInput, &MappedMouse)>,
+    mut sticks: Query<(&mut StickInput, &mut MappedMouse, Option<&InputContext>)>,
     mut mouse_motion_events: EventReader<MouseMotion>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut last_cursor: Local<Option<Vec2>>,
+    windows: Query<&Window>,
+    cursor_locked: Option<Res<CursorLocked>>,
+    contexts: Res<InputContextStack>,
+    time: Res<Time>,
 ) {
-    let delta = mouse_motion_events.iter().map(|e| e.delta).reduce(|v1, v2| v1 + v2).unwrap_or_default();
-    for (mut stick, &MappedMouse {sensitivity}) in sticks.iter_mut() {
-        let value = delta * sensitivity;
+    let locked = cursor_locked.map_or(true, |locked| locked.0);
+    let raw_delta = mouse_motion_events.iter().map(|e| e.delta).reduce(|v1, v2| v1 + v2).unwrap_or_default();
+    let mut cursor_delta = None;
+    for event in cursor_moved_events.iter() {
+        // physical pixels, not to depend on the scale factor of the window
+        let scale_factor = windows.get(event.window).map_or(1.0, |window| window.scale_factor() as f32);
+        let position = event.position * scale_factor;
+        if let Some(last) = *last_cursor {
+            cursor_delta = Some(cursor_delta.unwrap_or(Vec2::ZERO) + position - last);
+        }
+        *last_cursor = Some(position);
+    }
+    if !locked {
+        // the cursor jumps on locking again
+        *last_cursor = None;
+    }
+    for (mut stick, mut mapped, context) in sticks.iter_mut() {
+        if !locked || !contexts.is_active(context) {
+            mapped.speed = Vec2::ZERO;
+            // check real change for component change detection
+            if stick.value != Vec2::ZERO {
+                stick.value = Vec2::ZERO;
+            }
+            continue;
+        }
+        // locked cursors report no motion on most platforms
+        let delta = if mapped.raw {raw_delta} else {cursor_delta.unwrap_or(raw_delta)};
+        let gain = if 0.0 < time.delta_seconds() {
+            1.0 + mapped.acceleration * delta.length() / time.delta_seconds()
+        } else {
+            1.0
+        };
+        let smoothed = mapped.smooth(delta * gain, time.delta_seconds());
+        let value = smoothed * mapped.sensitivity;
         // check real change for component change detection
         if stick.value != value {
             stick.value = value;
@@ -90,6 +193,150 @@ fn update_mouse_mapped_sticks(
 }
 
 
+/// Pixels of a wheel notch in browsers, as wheels in lines report one line per notch mostly.
+pub const PIXELS_PER_LINE: f32 = 100.0;
+
+/// Converts the wheel delta into lines, positive for up and right.
+pub fn wheel_lines(event: &MouseWheel, pixels_per_line: f32) -> Vec2 {
+    let delta = Vec2::new(event.x, event.y);
+    match event.unit {
+        MouseScrollUnit::Line => delta,
+        MouseScrollUnit::Pixel => delta / pixels_per_line.max(f32::EPSILON),
+    }
+}
+
+/// Mouse wheel of the frame, in lines multiplied by `sensitivity`.
+/// Put on [`StickInput`] for both directions, or on [`AxisInput`] for the vertical one.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct MappedWheel {
+    pub sensitivity: Vec2,
+    /// Pixels counted as a line, for touchpads and browsers reporting in pixels.
+    pub pixels_per_line: f32,
+}
+impl MappedWheel {
+    pub fn new(sensitivity: Vec2) -> Self {
+        Self { sensitivity, pixels_per_line: PIXELS_PER_LINE }
+    }
+}
+impl Default for MappedWheel {
+    fn default() -> Self {
+        Self::new(Vec2::ONE)
+    }
+}
+
+fn update_wheel_mapped_inputs(
+    mut sticks: Query<(&mut StickInput, &MappedWheel, Option<&InputContext>)>,
+    mut axes: Query<(&mut AxisInput, &MappedWheel, Option<&InputContext>)>,
+    mut wheel_events: EventReader<MouseWheel>,
+    contexts: Res<InputContextStack>,
+) {
+    let events: Vec<&MouseWheel> = wheel_events.iter().collect();
+    let lines = |mapped: &MappedWheel| events.iter().map(|event| wheel_lines(event, mapped.pixels_per_line)).sum::<Vec2>();
+    for (mut stick, mapped, context) in sticks.iter_mut() {
+        let value = if contexts.is_active(context) {lines(mapped) * mapped.sensitivity} else {Vec2::ZERO};
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+    for (mut axis, mapped, context) in axes.iter_mut() {
+        let value = if contexts.is_active(context) {lines(mapped).y * mapped.sensitivity.y} else {0.0};
+        // check real change for component change detection
+        if axis.value != value {
+            axis.value = value;
+        }
+    }
+}
+
+
+/// Stick of any of connected gamepads.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct MappedGamepadStick {
+    pub x: GamepadAxisType,
+    pub y: GamepadAxisType,
+    pub sensitivity: Vec2,
+    raw: Vec2,
+}
+impl MappedGamepadStick {
+    pub fn new(x: GamepadAxisType, y: GamepadAxisType, sensitivity: Vec2) -> Self {
+        Self { x, y, sensitivity, raw: Vec2::ZERO }
+    }
+    pub fn left(sensitivity: Vec2) -> Self {
+        Self::new(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY, sensitivity)
+    }
+    pub fn right(sensitivity: Vec2) -> Self {
+        Self::new(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY, sensitivity)
+    }
+}
+
+fn update_gamepad_mapped_sticks(
+    mut sticks: Query<(&mut StickInput, &mut MappedGamepadStick, Option<&InputContext>)>,
+    mut gamepad_axis_events: EventReader<GamepadAxisChangedEvent>,
+    contexts: Res<InputContextStack>,
+) {
+    let events: Vec<&GamepadAxisChangedEvent> = gamepad_axis_events.iter().collect();
+    for (mut stick, mut mapped, context) in sticks.iter_mut() {
+        // axis events arrive only on change, so keep the latest raw values
+        for event in events.iter() {
+            if event.axis_type == mapped.x {
+                mapped.raw.x = event.value;
+            }
+            if event.axis_type == mapped.y {
+                mapped.raw.y = event.value;
+            }
+        }
+        let value = if contexts.is_active(context) {mapped.raw * mapped.sensitivity} else {Vec2::ZERO};
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+/// Analog value of gamepads, such as triggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceAxisCode {
+    GamepadAxis(GamepadAxisType),
+    /// Pressure of the button, mainly for triggers.
+    GamepadButton(GamepadButtonType),
+}
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct MappedGamepadAxis {
+    pub code: DeviceAxisCode,
+    pub sensitivity: f32,
+    raw: f32,
+}
+impl MappedGamepadAxis {
+    pub fn new(code: DeviceAxisCode, sensitivity: f32) -> Self {
+        Self { code, sensitivity, raw: 0.0 }
+    }
+}
+
+fn update_gamepad_mapped_axes(
+    mut axes: Query<(&mut AxisInput, &mut MappedGamepadAxis, Option<&InputContext>)>,
+    mut gamepad_axis_events: EventReader<GamepadAxisChangedEvent>,
+    mut gamepad_button_events: EventReader<GamepadButtonChangedEvent>,
+    contexts: Res<InputContextStack>,
+) {
+    let events: Vec<(DeviceAxisCode, f32)> = gamepad_axis_events.iter()
+        .map(|e| (DeviceAxisCode::GamepadAxis(e.axis_type), e.value))
+        .chain(gamepad_button_events.iter().map(|e| (DeviceAxisCode::GamepadButton(e.button_type), e.value)))
+        .collect();
+    for (mut axis, mut mapped, context) in axes.iter_mut() {
+        for &(code, value) in events.iter() {
+            if code == mapped.code {
+                mapped.raw = value;
+            }
+        }
+        let value = if contexts.is_active(context) {mapped.raw * mapped.sensitivity} else {0.0};
+        // check real change for component change detection
+        if axis.value != value {
+            axis.value = value;
+        }
+    }
+}
+
+
 #[derive(Debug, Component, Clone, Copy, Default, PartialEq)]
 pub struct MaxLength {
     pub value: f32,
@@ -102,19 +349,26 @@ pub struct DeadZone {
 
 pub fn clamp_stick (
     mut sticks: Query<
-        (&mut StickInput, Option<&MaxLength>, Option<&DeadZone>),
-        (Or<(With<MaxLength>, With<DeadZone>)>, Changed<StickInput>)
+        (Entity, &mut StickInput, Option<&MaxLength>, Option<&DeadZone>),
+        Or<(With<MaxLength>, With<DeadZone>)>
     >,
+    order: Res<CascadeOrder>,
 ) {
-    for (mut stick, max_len, deadzone) in sticks.iter_mut() {
+    for (entity, mut stick, max_len, deadzone) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let mut value = stick.value;
         if let Some(max_len) = max_len {
-            stick.value = stick.value.clamp_length_max(max_len.value);
+            value = value.clamp_length_max(max_len.value);
         }
         if let Some(deadzone) = deadzone {
-            if stick.value.length() < deadzone.value {
-                stick.value = Vec2::ZERO;
+            if value.length() < deadzone.value {
+                value = Vec2::ZERO;
             }
         }
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
     }
 }
 
@@ -136,13 +390,20 @@ impl<S> MappedEulerAngle<S> where
         }
     }
 }
+impl<S> CascadeNode for MappedEulerAngle<S> where
+    S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
 pub fn update_rotation_from_euler<SystemLabel> (
-    mut dests: Query<(&mut RotationalInput, &MappedEulerAngle<SystemLabel>)>,
+    mut dests: Query<(Entity, &mut RotationalInput, &MappedEulerAngle<SystemLabel>)>,
     source: Query<&EulerAngleInput>,
+    order: Res<CascadeOrder>,
 ) where
     SystemLabel: Clone + Eq + Send + Sync + 'static
 {
-    for (mut rotation, mapping) in dests.iter_mut() {
+    for (entity, mut rotation, mapping) in dests.iter_mut() {
+        if !order.is_current(entity) {continue;}
         let Ok(source) = source.get(mapping.source) else {
             warn!("Entity not found");
             continue;
@@ -155,3 +416,88 @@ pub fn update_rotation_from_euler<SystemLabel> (
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        input::{InputPlugin, gamepad::{GamepadEvent, GamepadConnection, GamepadConnectionEvent, GamepadInfo}},
+        time::TimeUpdateStrategy,
+        window::WindowPlugin,
+    };
+
+    use super::*;
+    use crate::cascade_input::CascadeInputPlugin;
+
+    /// Turns by moving the mouse at `speed` counts per second for half a second, then resting,
+    /// sampling the total motion every 1/30 seconds.
+    fn look_totals(fps: u32, speed: Vec2) -> Vec<Vec2> {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / fps as f64)));
+        let mut mouse = MappedMouse::new(Vec2::splat(0.001));
+        mouse.acceleration = 0.002;
+        mouse.smoothing = 0.05;
+        let stick = app.world.spawn((StickInput::default(), mouse)).id();
+        // the first frame has no duration
+        app.update();
+
+        let mut total = Vec2::ZERO;
+        let mut totals = Vec::new();
+        for frame in 1..=fps {
+            if frame <= fps / 2 {
+                app.world.send_event(MouseMotion { delta: speed / fps as f32 });
+            }
+            app.update();
+            total += app.world.get::<StickInput>(stick).unwrap().value;
+            if frame % (fps / 30) == 0 {
+                totals.push(total);
+            }
+        }
+        totals
+    }
+
+    #[test]
+    fn mouse_look_is_independent_of_frame_rate() {
+        let speed = Vec2::new(800.0, -300.0);
+        let totals_30 = look_totals(30, speed);
+        let totals_240 = look_totals(240, speed);
+        assert_eq!(totals_30.len(), totals_240.len());
+        for (at_30, at_240) in totals_30.iter().zip(totals_240.iter()) {
+            assert!((*at_30 - *at_240).length() < 1e-4 * at_240.length().max(1.0), "{} at 30 FPS, {} at 240 FPS", at_30, at_240);
+        }
+        // the smoothing keeps the total motion
+        let gain = 1.0 + 0.002 * speed.length();
+        assert!((totals_240.last().unwrap().x - 0.5 * speed.x * gain * 0.001).abs() < 1e-3);
+    }
+
+    #[test]
+    fn gamepad_sticks_and_triggers_keep_the_latest_values() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin));
+        let gamepad = Gamepad::new(0);
+        app.world.send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo { name: "Test".to_string() }),
+        )));
+        let stick = app.world.spawn((StickInput::default(), MappedGamepadStick::left(Vec2::new(1.0, 2.0)))).id();
+        let trigger = app.world.spawn((AxisInput::default(), MappedGamepadAxis::new(DeviceAxisCode::GamepadButton(GamepadButtonType::RightTrigger2), 1.0))).id();
+        app.update();
+
+        app.world.send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(gamepad, GamepadAxisType::LeftStickX, 0.5)));
+        app.world.send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(gamepad, GamepadAxisType::LeftStickY, -0.25)));
+        app.world.send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(gamepad, GamepadButtonType::RightTrigger2, 0.4)));
+        app.update();
+        assert_eq!(app.world.get::<StickInput>(stick).unwrap().value, Vec2::new(0.5, -0.5));
+        assert_eq!(app.world.get::<AxisInput>(trigger).unwrap().value, 0.4);
+
+        // axis events arrive only on change
+        app.world.send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(gamepad, GamepadAxisType::LeftStickX, 0.0)));
+        app.update();
+        assert_eq!(app.world.get::<StickInput>(stick).unwrap().value, Vec2::new(0.0, -0.5));
+        assert_eq!(app.world.get::<AxisInput>(trigger).unwrap().value, 0.4);
+    }
+}
@@ -0,0 +1,135 @@
+//! Keeps presses pending, so that a press slightly before it is acceptable is not lost.
+//!
+//! [`BufferedPress`] remembers the last press of its source for `window` seconds.
+//! State machines check it with [`BufferedPressTrigger`], and the entered state takes it
+//! with [`consume_buffered_press`], so that one press makes at most one transition.
+//! The button itself follows the source, so that holding it means the same as without the buffer.
+
+use bevy::{prelude::*, input::ButtonState, ecs::system::{Command, EntityCommands}};
+use seldom_state::trigger::BoolTrigger;
+
+use super::{
+    button::ButtonInput,
+    order::{CascadeAppExt, CascadeNodeSet, CascadeNode, CascadeOrder},
+};
+
+
+pub struct BufferedPressPlugin;
+impl Plugin for BufferedPressPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_cascade_node::<BufferedPress, _>(CascadeNodeSet::Evaluate, update_buffered_presses)
+        ;
+    }
+}
+
+
+/// Follows the source, and keeps its press pending, apart from the state of the button.
+/// A press stays pending until consumed or `window` seconds passed.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct BufferedPress {
+    pub source: Entity,
+    pub window: f32,
+    /// Seconds since the pending press.
+    pending: Option<f32>,
+}
+impl BufferedPress {
+    pub fn new(source: Entity, window: f32) -> Self {
+        Self {
+            source: source,
+            window: window,
+            pending: None,
+        }
+    }
+    pub fn is_pending(&self) -> bool { self.pending.is_some() }
+    /// Takes the pending press, returns whether there was.
+    pub fn consume(&mut self) -> bool { self.pending.take().is_some() }
+    /// Sets pending as a new press, keeping the age of the press already pending.
+    pub fn set_pending(&mut self, pending: bool) {
+        self.pending = match (pending, self.pending) {
+            (true, Some(age)) => Some(age),
+            (true, None) => Some(0.0),
+            (false, _) => None,
+        };
+    }
+}
+impl CascadeNode for BufferedPress {
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+fn update_buffered_presses(
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut BufferedPress)>,
+    sources: Query<&ButtonInput, Without<BufferedPress>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut button, mut buffer) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = sources.get(buffer.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        let window = buffer.window;
+        let pending = match buffer.pending {
+            _ if source.just_pressed() => Some(0.0),
+            Some(age) if age + time.delta_seconds() <= window => Some(age + time.delta_seconds()),
+            _ => None,
+        };
+        // avoid false change detection
+        if buffer.pending != pending {
+            buffer.pending = pending;
+        }
+        // events of the frame too, for taps within a frame
+        for state in source.events() {
+            button.set(state);
+        }
+        let state = if source.pressed() {ButtonState::Pressed} else {ButtonState::Released};
+        // avoid false change detection
+        if !button.is(state) {
+            button.set(state);
+        }
+    }
+}
+
+
+/// Takes the pending press of the button, does nothing for buttons without [`BufferedPress`].
+pub struct ConsumeBufferedPress {
+    pub button: Entity,
+}
+impl Command for ConsumeBufferedPress {
+    fn apply(self, world: &mut World) {
+        let Some(mut buffer) = world.get_mut::<BufferedPress>(self.button) else {return;};
+        if buffer.is_pending() {
+            buffer.consume();
+        }
+    }
+}
+
+/// Callback for `on_enter` of the state entered by [`BufferedPressTrigger`].
+pub fn consume_buffered_press(button: Entity) -> impl Fn(&mut EntityCommands) + Send + Sync + 'static {
+    move |commands| commands.commands().add(ConsumeBufferedPress { button: button })
+}
+
+
+/// While a press of the button is pending.
+/// Falls back to [`ButtonJustPressedTrigger`](super::button::ButtonJustPressedTrigger) for buttons without [`BufferedPress`].
+#[derive(Clone, Copy)]
+pub struct BufferedPressTrigger {
+    pub button: Entity,
+}
+impl BoolTrigger for BufferedPressTrigger {
+    type Param<'w, 's> = Query<'w, 's, (&'static ButtonInput, Option<&'static BufferedPress>)>;
+    fn trigger(
+        &self,
+        _entity: Entity,
+        buttons: Self::Param<'_, '_>,
+    ) -> bool {
+        let Ok((button, buffer)) = buttons.get(self.button) else {
+            warn!("Entity not found!");
+            return false;
+        };
+        match buffer {
+            Some(buffer) => buffer.is_pending(),
+            None => button.just_pressed(),
+        }
+    }
+}
@@ -2,18 +2,26 @@ use std::marker::PhantomData;
 
 use bevy::{
     prelude::*,
-    input::{keyboard::KeyboardInput, ButtonState, mouse::MouseButtonInput},
+    input::{
+        keyboard::KeyboardInput, ButtonState, mouse::{MouseButtonInput, MouseWheel},
+        gamepad::{GamepadButtonChangedEvent, GamepadSettings, GamepadButton},
+    },
 };
 use seldom_state::trigger::BoolTrigger;
+use serde::{Serialize, Deserialize};
 
-use super::CascadeInputSet;
+use super::{CascadeInputSet, axis::{wheel_lines, PIXELS_PER_LINE}, record::replaying, context::{InputContext, InputContextStack}, order::{CascadeNode, CascadeOrder}};
 
 
 pub struct ButtonInputPlugin;
 impl Plugin for ButtonInputPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(PreUpdate, update_key_mapped_buttons.in_set(CascadeInputSet::DeviceMappedInputs))
+            .add_systems(PreUpdate,
+                update_key_mapped_buttons
+                .in_set(CascadeInputSet::DeviceMappedInputs)
+                .run_if(not(replaying))
+            )
             .add_systems(PostUpdate, clear_button_events.in_set(CascadeInputSet::Clear))
         ;
     }
@@ -53,6 +61,11 @@ impl ButtonInput {
     pub fn just_pressed(&self) -> bool { self.events.contains(&ButtonState::Pressed) }
     pub fn just_released(&self) -> bool { self.events.contains(&ButtonState::Released) }
     pub fn events(&self) -> Vec<ButtonState> { self.events.clone() }
+    /// Overwrites the state and the events of the frame, such as to restore a snapshot.
+    pub fn restore(&mut self, state: ButtonState, events: Vec<ButtonState>) {
+        self.state = state;
+        self.events = events;
+    }
 }
 
 fn clear_button_events (
@@ -101,13 +114,44 @@ impl BoolTrigger for ButtonJustPressedTrigger {
         button.just_pressed()
     }
 }
+#[derive(Clone, Copy)]
+pub struct ButtonJustReleasedTrigger {
+    pub button: Entity,
+}
+impl BoolTrigger for ButtonJustReleasedTrigger {
+    type Param<'w, 's> = Query<'w, 's, &'static ButtonInput>;
+    fn trigger(
+        &self,
+        _entity: Entity,
+        buttons: Self::Param<'_, '_>,
+    ) -> bool {
+        let Ok(button) = buttons.get(self.button) else {
+            warn!("Entity not found!");
+            return false;
+        };
+        button.just_released()
+    }
+}
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeviceButtonCode {
     Key(KeyCode),
+    /// `Other(n)` for extra buttons, such as thumb buttons for back and forward.
     Mouse(MouseButton),
-    // Gamepad(GamepadButtonType)
+    /// Tapped, just pressed and released, once per line of the wheel.
+    /// Lines beyond one in a frame are carried over, so that each frame taps once.
+    Wheel(WheelDirection),
+    /// Button on any of connected gamepads.
+    Gamepad(GamepadButtonType),
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WheelDirection {
+    Up,
+    Down,
+    Left,
+    Right,
 }
+
 #[derive(Component)]
 pub struct MappedDeviceButton {
     pub code: DeviceButtonCode,
@@ -121,32 +165,72 @@ impl MappedDeviceButton {
 }
 
 fn update_key_mapped_buttons (
-    mut buttons: Query<(&mut ButtonInput, &MappedDeviceButton)>,
+    mut buttons: Query<(&mut ButtonInput, &MappedDeviceButton, Option<&InputContext>)>,
     mut keyboard_input_events: EventReader<KeyboardInput>,
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut gamepad_button_events: EventReader<GamepadButtonChangedEvent>,
+    gamepad_settings: Res<GamepadSettings>,
+    contexts: Res<InputContextStack>,
+    mut wheel_lines_left: Local<Vec2>,
 ) {
-    for event in keyboard_input_events.iter() {
-        let Some(key_code) = event.key_code else {continue;};
-        for (mut button, mapped_button) in buttons.iter_mut() {
-            if DeviceButtonCode::Key(key_code) == mapped_button.code {
-                // avoid false change detection
-                if !button.is(event.state) {
-                    button.set(event.state)
-                }
-            }
+    // release buttons left pressed by deactivated contexts
+    for (mut button, _, context) in buttons.iter_mut() {
+        if button.pressed() && !contexts.is_active(context) {
+            button.release();
         }
     }
-    for event in mouse_button_input_events.iter() {
-        let button_code = event.button;
-        for (mut button, mapped_button) in buttons.iter_mut() {
-            if DeviceButtonCode::Mouse(button_code) == mapped_button.code {
-                // avoid false change detection
-                if !button.is(event.state) {
-                    button.set(event.state)
-                }
+    let keyboard_events = keyboard_input_events.iter().filter_map(|event|
+        event.key_code.map(|key_code| (DeviceButtonCode::Key(key_code), event.state))
+    );
+    let mouse_events = mouse_button_input_events.iter().map(|event|
+        (DeviceButtonCode::Mouse(event.button), event.state)
+    );
+    let gamepad_events = gamepad_button_events.iter().filter_map(|event| {
+        let settings = gamepad_settings.get_button_settings(GamepadButton::new(event.gamepad, event.button_type));
+        let state = if settings.press_threshold() <= event.value {
+            ButtonState::Pressed
+        } else if event.value <= settings.release_threshold() {
+            ButtonState::Released
+        } else {
+            return None;   // between thresholds, keep current state
+        };
+        Some((DeviceButtonCode::Gamepad(event.button_type), state))
+    });
+    let mut events: Vec<(DeviceButtonCode, ButtonState)> = keyboard_events.chain(mouse_events).chain(gamepad_events).collect();
+    // pixels of touchpads add up to lines over frames
+    for event in mouse_wheel_events.iter() {
+        *wheel_lines_left += wheel_lines(event, PIXELS_PER_LINE);
+    }
+    let wheel_lines_left = &mut *wheel_lines_left;
+    for (lines, negative, positive) in [
+        (&mut wheel_lines_left.x, WheelDirection::Left, WheelDirection::Right),
+        (&mut wheel_lines_left.y, WheelDirection::Down, WheelDirection::Up),
+    ] {
+        // one tap per frame, since taps in the same frame collapse into one just pressed
+        if 1.0 <= lines.abs() {
+            let direction = if *lines < 0.0 {negative} else {positive};
+            events.extend([(DeviceButtonCode::Wheel(direction), ButtonState::Pressed), (DeviceButtonCode::Wheel(direction), ButtonState::Released)]);
+            *lines -= lines.signum();
+        }
+    }
+    for (code, state) in events {
+        let binds = |context: &InputContext| buttons.iter().any(|(_, mapped_button, c)| mapped_button.code == code && c == Some(context));
+        let receiver = contexts.receiver(binds);
+        for (mut button, mapped_button, context) in buttons.iter_mut() {
+            if code != mapped_button.code {
+                continue;
+            }
+            if context.is_some() && context.copied() != receiver {
+                continue;
+            }
+            // avoid false change detection
+            if !button.is(state) {
+                button.set(state)
             }
         }
-    }}
+    }
+}
 
 
 
@@ -167,13 +251,20 @@ impl<S> Toggle<S>
         }
     }
 }
+impl<S> CascadeNode for Toggle<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
 pub fn update_toggle_buttons<SystemLabel> (
-    mut buttons: Query<(&mut ButtonInput, &Toggle<SystemLabel>)>,
-    source: Query<&ButtonInput, (Changed<ButtonInput>, Without<Toggle<SystemLabel>>)>,
+    mut buttons: Query<(Entity, &mut ButtonInput, &Toggle<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<Toggle<SystemLabel>>>,
+    order: Res<CascadeOrder>,
 ) where
     SystemLabel: Clone + Eq + Send + Sync + 'static
 {
-    for (mut button, toggle) in buttons.iter_mut() {
+    for (entity, mut button, toggle) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
         let Ok(source) = source.get(toggle.source) else {continue;};
         if source.just_pressed() {
             if button.pressed() {
@@ -184,3 +275,98 @@ pub fn update_toggle_buttons<SystemLabel> (
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{InputPlugin, mouse::MouseScrollUnit, gamepad::{GamepadEvent, GamepadConnection, GamepadConnectionEvent, GamepadInfo}};
+
+    use super::*;
+    use crate::cascade_input::CascadeInputPlugin;
+
+    fn app_with_gamepad() -> (App, Gamepad) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, bevy::window::WindowPlugin::default(), CascadeInputPlugin));
+        let gamepad = Gamepad::new(0);
+        app.world.send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo { name: "Test".to_string() }),
+        )));
+        app.update();
+        (app, gamepad)
+    }
+
+    #[test]
+    fn gamepad_buttons_follow_thresholds() {
+        let (mut app, gamepad) = app_with_gamepad();
+        let south = app.world.spawn((ButtonInput::default(), MappedDeviceButton::new(DeviceButtonCode::Gamepad(GamepadButtonType::South)))).id();
+        let east = app.world.spawn((ButtonInput::default(), MappedDeviceButton::new(DeviceButtonCode::Gamepad(GamepadButtonType::East)))).id();
+        let button = |app: &App, entity: Entity| app.world.get::<ButtonInput>(entity).unwrap().clone();
+        let send = |app: &mut App, value: f32| {
+            app.world.send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(gamepad, GamepadButtonType::South, value)));
+            app.update();
+        };
+
+        send(&mut app, 1.0);
+        assert!(button(&app, south).pressed());
+        assert!(button(&app, east).released());
+        // events are cleared at the end of the frame
+        assert!(button(&app, south).events().is_empty());
+
+        // between the release and press thresholds
+        send(&mut app, 0.7);
+        assert!(button(&app, south).pressed());
+
+        send(&mut app, 0.0);
+        assert!(button(&app, south).released());
+    }
+
+    #[test]
+    fn wheel_lines_tap_once_per_frame() {
+        /// Taps seen by the game in the frame, before the events are cleared.
+        #[derive(Resource, Default)]
+        struct Taps(Vec<usize>);
+        fn count_taps(mut taps: ResMut<Taps>, buttons: Query<&ButtonInput>) {
+            let button = buttons.single();
+            taps.0.push(if button.just_pressed() && button.just_released() {1} else {0});
+        }
+
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, bevy::window::WindowPlugin::default(), CascadeInputPlugin))
+            .init_resource::<Taps>()
+            .add_systems(Update, count_taps);
+        app.world.spawn((ButtonInput::default(), MappedDeviceButton::new(DeviceButtonCode::Wheel(WheelDirection::Up))));
+        let wheel = |unit, y| MouseWheel { unit, x: 0.0, y, window: Entity::PLACEHOLDER };
+        app.world.send_event(wheel(MouseScrollUnit::Line, 3.0));
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<Taps>().0, [1, 1, 1, 0]);
+
+        // pixels add up to a line, and scrolling down takes back the lines left
+        app.world.resource_mut::<Taps>().0.clear();
+        app.world.send_event(wheel(MouseScrollUnit::Pixel, 0.6 * PIXELS_PER_LINE));
+        app.update();
+        app.world.send_event(wheel(MouseScrollUnit::Pixel, 0.6 * PIXELS_PER_LINE));
+        app.world.send_event(wheel(MouseScrollUnit::Line, 2.0));
+        app.world.send_event(wheel(MouseScrollUnit::Line, -1.0));
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<Taps>().0, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn gamepad_buttons_are_just_pressed_within_the_frame() {
+        let (mut app, gamepad) = app_with_gamepad();
+        let south = app.world.spawn((ButtonInput::default(), MappedDeviceButton::new(DeviceButtonCode::Gamepad(GamepadButtonType::South)))).id();
+        app.world.send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(gamepad, GamepadButtonType::South, 1.0)));
+        // run up to the cascade, before the events are cleared
+        app.world.run_schedule(First);
+        app.world.run_schedule(PreUpdate);
+        let button = app.world.get::<ButtonInput>(south).unwrap();
+        assert!(button.pressed());
+        assert!(button.just_pressed());
+    }
+}
@@ -0,0 +1,210 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+
+use super::{button::ButtonInput, order::{CascadeNode, CascadeOrder}};
+
+
+/// Pressed while all of the sources are pressed.
+/// If `ordered`, the sources need to be pressed in the order, simultaneous presses are taken as ordered.
+#[derive(Component)]
+pub struct Chord<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    pub sources: Vec<Entity>,
+    pub ordered: bool,
+    press_order: Vec<Entity>,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> Chord<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn new(sources: Vec<Entity>) -> Self {
+        Self {
+            sources: sources,
+            ordered: false,
+            press_order: Vec::default(),
+            _phantom: PhantomData,
+        }
+    }
+    pub fn ordered(sources: Vec<Entity>) -> Self {
+        Self {
+            ordered: true,
+            ..Self::new(sources)
+        }
+    }
+}
+impl<S> CascadeNode for Chord<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { self.sources.clone() }
+}
+pub fn update_chord_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut Chord<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<Chord<SystemLabel>>>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, mut chord) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(sources) = chord.sources.iter().map(|&entity| source.get(entity).map(|s| (entity, s))).collect::<Result<Vec<_>, _>>() else {
+            warn!("Entity not found");
+            continue;
+        };
+        let mut press_order = chord.press_order.clone();
+        press_order.retain(|entity| sources.iter().any(|(e, s)| e == entity && s.pressed()));
+        for (entity, source) in sources.iter() {
+            if source.pressed() && !press_order.contains(entity) {
+                press_order.push(*entity);
+            }
+        }
+        let all_pressed = press_order.len() == chord.sources.len();
+        let in_order = !chord.ordered || press_order == chord.sources;
+        // keep pressed until any of the sources released
+        let pressed = all_pressed && (in_order || button.pressed());
+        // avoid false change detection
+        if chord.press_order != press_order {
+            chord.press_order = press_order;
+        }
+        if button.pressed() != pressed {
+            if pressed { button.press(); } else { button.release(); }
+        }
+    }
+}
+
+
+/// Passes the source depending on the modifier is held or not, when the source is pressed.
+///
+/// Pair `Modifier::with(key, shift)` and `Modifier::without(key, shift)`
+/// to separate Shift+key from the bare key.
+#[derive(Component)]
+pub struct Modifier<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    pub source: Entity,
+    pub modifier: Entity,
+    /// Whether the modifier needs to be held, or not.
+    pub held: bool,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> Modifier<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn with(source: Entity, modifier: Entity) -> Self {
+        Self {
+            source: source,
+            modifier: modifier,
+            held: true,
+            _phantom: PhantomData,
+        }
+    }
+    pub fn without(source: Entity, modifier: Entity) -> Self {
+        Self {
+            held: false,
+            ..Self::with(source, modifier)
+        }
+    }
+}
+impl<S> CascadeNode for Modifier<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source, self.modifier] }
+}
+pub fn update_modifier_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &Modifier<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<Modifier<SystemLabel>>>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, modifier) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let (Ok(source), Ok(modifier_button)) = (source.get(modifier.source), source.get(modifier.modifier)) else {
+            warn!("Entity not found");
+            continue;
+        };
+        // decided at the press, to keep the state while the source is held
+        if source.just_pressed() && modifier_button.pressed() == modifier.held && button.released() {
+            button.press();
+        }
+        // avoid false change detection
+        if source.released() && button.pressed() {
+            button.release();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::{input::InputPlugin, window::WindowPlugin};
+
+    use super::*;
+    use crate::cascade_input::{CascadeInputPlugin, order::{CascadeAppExt, CascadeNodeSet}};
+
+    #[derive(Clone, PartialEq, Eq)]
+    struct TestLabel;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+            .add_cascade_node::<Chord<TestLabel>, _>(CascadeNodeSet::Evaluate, update_chord_buttons::<TestLabel>)
+            .add_cascade_node::<Modifier<TestLabel>, _>(CascadeNodeSet::Evaluate, update_modifier_buttons::<TestLabel>);
+        app
+    }
+
+    /// Sets the buttons, runs a frame and returns whether the outputs are pressed.
+    fn step(app: &mut App, buttons: &[(Entity, bool)], outputs: &[Entity]) -> Vec<bool> {
+        for &(entity, pressed) in buttons {
+            let mut button = app.world.get_mut::<ButtonInput>(entity).unwrap();
+            if pressed { button.press(); } else { button.release(); }
+        }
+        app.update();
+        outputs.iter().map(|&output| app.world.get::<ButtonInput>(output).unwrap().pressed()).collect()
+    }
+
+    #[test]
+    fn ordered_chords_need_the_order() {
+        let mut app = app();
+        let first = app.world.spawn(ButtonInput::default()).id();
+        let second = app.world.spawn(ButtonInput::default()).id();
+        let chord = app.world.spawn((ButtonInput::default(), Chord::<TestLabel>::new(vec![first, second]))).id();
+        let ordered = app.world.spawn((ButtonInput::default(), Chord::<TestLabel>::ordered(vec![first, second]))).id();
+        let outputs = [chord, ordered];
+
+        assert_eq!(step(&mut app, &[(first, true)], &outputs), [false, false]);
+        assert_eq!(step(&mut app, &[(second, true)], &outputs), [true, true]);
+        // kept until any released
+        assert_eq!(step(&mut app, &[(first, false)], &outputs), [false, false]);
+
+        // out of order
+        assert_eq!(step(&mut app, &[(first, true)], &outputs), [true, false]);
+        assert_eq!(step(&mut app, &[(first, false), (second, false)], &outputs), [false, false]);
+
+        // simultaneous presses are taken as ordered
+        assert_eq!(step(&mut app, &[(first, true), (second, true)], &outputs), [true, true]);
+    }
+
+    #[test]
+    fn modifier_suppresses_the_bare_key() {
+        let mut app = app();
+        let key = app.world.spawn(ButtonInput::default()).id();
+        let shift = app.world.spawn(ButtonInput::default()).id();
+        let with = app.world.spawn((ButtonInput::default(), Modifier::<TestLabel>::with(key, shift))).id();
+        let without = app.world.spawn((ButtonInput::default(), Modifier::<TestLabel>::without(key, shift))).id();
+        let outputs = [with, without];
+
+        assert_eq!(step(&mut app, &[(key, true)], &outputs), [false, true]);
+        // pressing the modifier later keeps the bare key
+        assert_eq!(step(&mut app, &[(shift, true)], &outputs), [false, true]);
+        assert_eq!(step(&mut app, &[(key, false)], &outputs), [false, false]);
+
+        // the bare key is suppressed while the modifier is held
+        assert_eq!(step(&mut app, &[(key, true)], &outputs), [true, false]);
+        // releasing the modifier keeps the modified key
+        assert_eq!(step(&mut app, &[(shift, false)], &outputs), [true, false]);
+        assert_eq!(step(&mut app, &[(key, false)], &outputs), [false, false]);
+    }
+}
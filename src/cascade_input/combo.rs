@@ -0,0 +1,244 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+
+use super::{button::ButtonInput, axis::StickInput, order::{CascadeNode, CascadeOrder}};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComboStep {
+    /// The button is just pressed.
+    Press(Entity),
+    /// The stick enters the direction, within `tolerance` radians and longer than `min_length`.
+    Direction {
+        stick: Entity,
+        direction: Vec2,
+        tolerance: f32,
+        min_length: f32,
+    },
+}
+impl ComboStep {
+    pub fn direction(stick: Entity, direction: Vec2) -> Self {
+        Self::Direction {
+            stick,
+            direction,
+            tolerance: std::f32::consts::FRAC_PI_4,
+            min_length: 0.5,
+        }
+    }
+    fn source(&self) -> Entity {
+        match *self {
+            Self::Press(button) => button,
+            Self::Direction { stick, .. } => stick,
+        }
+    }
+}
+
+/// Just pressed and released when the steps arrive in order within `window` seconds.
+///
+/// Other steps arriving out of order reset the progress.
+#[derive(Component)]
+pub struct Combo<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    steps: Vec<ComboStep>,
+    pub window: f32,
+    progress: usize,
+    elapsed: f32,
+    /// Whether each direction step matched on the last frame, to detect entering.
+    matched: Vec<bool>,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> Combo<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn new(steps: Vec<ComboStep>, window: f32) -> Self {
+        let len = steps.len();
+        Self {
+            steps: steps,
+            window: window,
+            progress: 0,
+            elapsed: 0.0,
+            matched: vec![false; len],
+            _phantom: PhantomData,
+        }
+    }
+    pub fn steps(&self) -> &[ComboStep] { &self.steps }
+    /// Replaces the steps, and starts over.
+    pub fn set_steps(&mut self, steps: Vec<ComboStep>) {
+        *self = Self::new(steps, self.window);
+    }
+}
+impl<S> CascadeNode for Combo<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> {
+        self.steps.iter().map(|step| step.source()).collect()
+    }
+}
+pub fn update_combo_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut Combo<SystemLabel>)>,
+    source_buttons: Query<&ButtonInput, Without<Combo<SystemLabel>>>,
+    source_sticks: Query<&StickInput>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, mut combo) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        if combo.steps.is_empty() {continue;}
+        let matching: Option<Vec<bool>> = combo.steps.iter().map(|step| match *step {
+            ComboStep::Press(entity) => source_buttons.get(entity).ok().map(|source| source.just_pressed()),
+            ComboStep::Direction { stick, direction, tolerance, min_length } => source_sticks.get(stick).ok().map(|source| {
+                min_length <= source.value.length() && source.value.angle_between(direction).abs() <= tolerance
+            }),
+        }).collect();
+        let Some(matching) = matching else {
+            warn!("Entity not found");
+            continue;
+        };
+        // buttons hit while just pressed, sticks hit on entering the direction
+        let hits: Vec<bool> = combo.steps.iter().zip(matching.iter().zip(combo.matched.iter()))
+            .map(|(step, (&now, &before))| match step {
+                ComboStep::Press(_) => now,
+                ComboStep::Direction { .. } => now && !before,
+            })
+            .collect();
+        combo.matched = matching;
+
+        if 0 < combo.progress {
+            combo.elapsed += time.delta_seconds();
+            if combo.window < combo.elapsed {
+                combo.progress = 0;
+            }
+        }
+        let expected = combo.steps[combo.progress].source();
+        if hits[combo.progress] {
+            if combo.progress == 0 {
+                combo.elapsed = 0.0;
+            }
+            combo.progress += 1;
+        } else if hits.iter().zip(combo.steps.iter()).any(|(&hit, step)| hit && step.source() != expected) {
+            // wrong input, may start over
+            combo.progress = if hits[0] {1} else {0};
+            combo.elapsed = 0.0;
+        }
+        if combo.progress == combo.steps.len() {
+            combo.progress = 0;
+            button.press();
+            button.release();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{input::InputPlugin, time::TimeUpdateStrategy, window::WindowPlugin};
+
+    use super::*;
+    use crate::cascade_input::{CascadeInputPlugin, order::{CascadeAppExt, CascadeNodeSet}};
+
+    #[derive(Clone, PartialEq, Eq)]
+    struct TestLabel;
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    /// Just pressed counts of the combo, before the events are cleared.
+    #[derive(Resource, Default)]
+    struct Fired(usize);
+
+    fn count_fired(
+        mut fired: ResMut<Fired>,
+        combos: Query<&ButtonInput, With<Combo<TestLabel>>>,
+    ) {
+        fired.0 += combos.iter().filter(|combo| combo.just_pressed()).count();
+    }
+
+    struct Pad {
+        app: App,
+        stick: Entity,
+        fire: Entity,
+        combo: Entity,
+    }
+    impl Pad {
+        /// "back, forward, fire" within the window.
+        fn new(window: f32) -> Self {
+            let mut app = App::new();
+            app
+                .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+                .add_cascade_node::<Combo<TestLabel>, _>(CascadeNodeSet::Evaluate, update_combo_buttons::<TestLabel>)
+                .init_resource::<Fired>()
+                .add_systems(Update, count_fired)
+                .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(FRAME)));
+            let stick = app.world.spawn(StickInput::default()).id();
+            let fire = app.world.spawn(ButtonInput::default()).id();
+            let steps = vec![ComboStep::direction(stick, Vec2::NEG_Y), ComboStep::direction(stick, Vec2::Y), ComboStep::Press(fire)];
+            let combo = app.world.spawn((ButtonInput::default(), Combo::<TestLabel>::new(steps, window))).id();
+            app.update();
+            Self { app, stick, fire, combo }
+        }
+        fn frames(&mut self, stick: Vec2, fire: bool, frames: usize) {
+            for _ in 0..frames {
+                self.app.world.get_mut::<StickInput>(self.stick).unwrap().value = stick;
+                let mut button = self.app.world.get_mut::<ButtonInput>(self.fire).unwrap();
+                if fire { button.press(); } else { button.release(); }
+                self.app.update();
+            }
+        }
+        /// Back, forward, then fire, each held for the frames.
+        fn perform(&mut self, frames: usize) {
+            self.frames(Vec2::NEG_Y, false, frames);
+            self.frames(Vec2::Y, false, frames);
+            self.frames(Vec2::ZERO, true, frames);
+            self.frames(Vec2::ZERO, false, frames);
+        }
+        fn fired(&self) -> usize {
+            self.app.world.resource::<Fired>().0
+        }
+    }
+
+    #[test]
+    fn back_forward_fire_fires_once() {
+        let mut pad = Pad::new(0.5);
+        pad.perform(5);
+        assert_eq!(pad.fired(), 1);
+
+        // missing forward
+        pad.frames(Vec2::NEG_Y, false, 5);
+        pad.frames(Vec2::ZERO, true, 5);
+        assert_eq!(pad.fired(), 1);
+
+        // out of order
+        pad.frames(Vec2::Y, false, 5);
+        pad.frames(Vec2::NEG_Y, false, 5);
+        pad.frames(Vec2::ZERO, true, 5);
+        assert_eq!(pad.fired(), 1);
+    }
+
+    #[test]
+    fn slow_steps_time_out() {
+        let mut pad = Pad::new(0.5);
+        // fire arrives 0.6 seconds after back
+        pad.perform(18);
+        assert_eq!(pad.fired(), 0);
+
+        // starting over in time
+        pad.perform(10);
+        assert_eq!(pad.fired(), 1);
+    }
+
+    #[test]
+    fn replacing_steps_starts_over() {
+        let mut pad = Pad::new(0.5);
+        pad.frames(Vec2::NEG_Y, false, 2);
+        pad.frames(Vec2::Y, false, 2);
+        let fire = pad.fire;
+        pad.app.world.get_mut::<Combo<TestLabel>>(pad.combo).unwrap().set_steps(vec![ComboStep::Press(fire)]);
+        pad.frames(Vec2::ZERO, true, 1);
+        assert_eq!(pad.fired(), 1);
+    }
+}
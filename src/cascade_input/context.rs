@@ -0,0 +1,56 @@
+//! Scopes device-mapped inputs into stackable contexts.
+//!
+//! Device-mapped inputs with [`InputContext`] react only while the context is active in [`InputContextStack`].
+//! A device button goes to the topmost active context binding it, so lower contexts never see it.
+//! Inputs without [`InputContext`] are always active.
+
+use bevy::prelude::*;
+
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputContext(pub &'static str);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputContextLayer {
+    pub context: InputContext,
+    /// Blocks every input of lower layers, like modal menus.
+    pub exclusive: bool,
+}
+
+/// Active contexts, the last is the top.
+#[derive(Debug, Resource, Clone, Default)]
+pub struct InputContextStack {
+    layers: Vec<InputContextLayer>,
+}
+impl InputContextStack {
+    pub fn new(layers: Vec<InputContextLayer>) -> Self {
+        Self { layers }
+    }
+    pub fn push(&mut self, context: InputContext, exclusive: bool) {
+        self.layers.push(InputContextLayer { context, exclusive });
+    }
+    /// Removes the topmost layer of the context.
+    pub fn remove(&mut self, context: InputContext) -> Option<InputContextLayer> {
+        let index = self.layers.iter().rposition(|layer| layer.context == context)?;
+        Some(self.layers.remove(index))
+    }
+    pub fn layers(&self) -> &[InputContextLayer] {
+        &self.layers
+    }
+
+    /// Layers not blocked by exclusive ones, from the top.
+    fn active_layers(&self) -> impl Iterator<Item = &InputContextLayer> {
+        let bottom = self.layers.iter().rposition(|layer| layer.exclusive).unwrap_or(0);
+        self.layers[bottom..].iter().rev()
+    }
+    pub fn is_active(&self, context: Option<&InputContext>) -> bool {
+        let Some(context) = context else {
+            return true;
+        };
+        self.active_layers().any(|layer| layer.context == *context)
+    }
+    /// Topmost active context among the ones binding the input.
+    pub fn receiver(&self, binds: impl Fn(&InputContext) -> bool) -> Option<InputContext> {
+        self.active_layers().map(|layer| layer.context).find(|context| binds(context))
+    }
+}
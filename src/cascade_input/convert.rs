@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+
+use super::{
+    button::ButtonInput,
+    axis::{StickInput, AxisInput, StickButtons},
+    order::{CascadeAppExt, CascadeNodeSet, CascadeNode, CascadeOrder},
+};
+
+
+pub struct ConvertInputPlugin;
+impl Plugin for ConvertInputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_cascade_node::<Threshold, _>(CascadeNodeSet::Evaluate, update_threshold_buttons)
+            .add_cascade_node::<Ramp, _>(CascadeNodeSet::Evaluate, update_ramp_sticks)
+        ;
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdSource {
+    /// Length of the stick.
+    Stick(Entity),
+    /// Length of the stick along the direction, negative to the opposite.
+    Direction {
+        stick: Entity,
+        direction: Vec2,
+    },
+    /// Analog value, such as triggers.
+    Axis(Entity),
+}
+impl ThresholdSource {
+    fn entity(&self) -> Entity {
+        match *self {
+            Self::Stick(stick) | Self::Direction { stick, .. } => stick,
+            Self::Axis(axis) => axis,
+        }
+    }
+}
+
+/// Pressed when the source reaches `press`, and released when it falls below `release`.
+/// Keep `release` lower than `press` so that noise around the threshold never chatters.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub source: ThresholdSource,
+    pub press: f32,
+    pub release: f32,
+}
+impl Threshold {
+    pub fn new(source: ThresholdSource, press: f32, release: f32) -> Self {
+        Self {
+            source: source,
+            press: press,
+            release: release,
+        }
+    }
+}
+impl CascadeNode for Threshold {
+    fn sources(&self) -> Vec<Entity> { vec![self.source.entity()] }
+}
+fn update_threshold_buttons(
+    mut buttons: Query<(Entity, &mut ButtonInput, &Threshold)>,
+    sticks: Query<&StickInput>,
+    axes: Query<&AxisInput>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut button, threshold) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let value = match threshold.source {
+            ThresholdSource::Stick(stick) => sticks.get(stick).map(|stick| stick.value.length()).ok(),
+            ThresholdSource::Direction { stick, direction } => sticks.get(stick).map(|stick| stick.value.dot(direction.normalize_or_zero())).ok(),
+            ThresholdSource::Axis(axis) => axes.get(axis).map(|axis| axis.value).ok(),
+        };
+        let Some(value) = value else {
+            warn!("Entity not found");
+            continue;
+        };
+        // avoid false change detection
+        if button.released() && threshold.press <= value {
+            button.press();
+        } else if button.pressed() && value < threshold.release {
+            button.release();
+        }
+    }
+}
+
+
+/// Stick from buttons, moving toward the pressed direction at `acceleration` per second
+/// and back to zero at `deceleration` per second, like an analog stick on the keyboard.
+#[derive(Component)]
+pub struct Ramp {
+    pub buttons: StickButtons,
+    pub acceleration: f32,
+    pub deceleration: f32,
+}
+impl CascadeNode for Ramp {
+    fn sources(&self) -> Vec<Entity> { self.buttons.sources() }
+}
+fn update_ramp_sticks(
+    mut sticks: Query<(Entity, &mut StickInput, &Ramp)>,
+    buttons: Query<&ButtonInput>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, ramp) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let src = &ramp.buttons;
+        let (
+            Ok(negative_x), Ok(positive_x), Ok(negative_y), Ok(positive_y)
+        ) = (
+            buttons.get(src.negative_x), buttons.get(src.positive_x), buttons.get(src.negative_y), buttons.get(src.positive_y),
+        ) else {
+            warn!("Buttons not found");
+            continue;
+        };
+        let target = Vec2::new(
+            button_axis(negative_x, positive_x),
+            button_axis(negative_y, positive_y),
+        );
+        let delta = time.delta_seconds();
+        let value = Vec2::new(
+            ramp_axis(stick.value.x, target.x, ramp.acceleration * delta, ramp.deceleration * delta),
+            ramp_axis(stick.value.y, target.y, ramp.acceleration * delta, ramp.deceleration * delta),
+        );
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+fn button_axis(negative: &ButtonInput, positive: &ButtonInput) -> f32 {
+    (if negative.pressed() {-1.0} else {0.0}) + if positive.pressed() {1.0} else {0.0}
+}
+/// Accelerates while growing toward the target, decelerates otherwise, including reversing.
+fn ramp_axis(value: f32, target: f32, acceleration: f32, deceleration: f32) -> f32 {
+    let growing = target != 0.0 && (value == 0.0 || value.signum() == target.signum()) && value.abs() < target.abs();
+    let step = if growing {acceleration} else {deceleration};
+    value + (target - value).clamp(-step, step)
+}
@@ -0,0 +1,339 @@
+//! Dumps the cascade for debugging.
+//!
+//! [`CascadeGraph::capture`] takes a snapshot of every input and the references between them,
+//! which works without window, so tests can compare it as DOT or JSON.
+//! With [`CascadeDebugPlugin`], F3 toggles the text for an on-screen panel in [`CascadeDebugPanel`],
+//! and F9 exports the graph into `cascade.dot` and `cascade.json`.
+//! The keys are device-mapped buttons in the context of the plugin, so that they are blocked like other inputs, such as by menus.
+
+use std::fmt::Write;
+
+use bevy::{prelude::*, input::ButtonState};
+use serde::Serialize;
+
+use super::{
+    CascadeInputSet,
+    button::{ButtonInput, MappedDeviceButton, DeviceButtonCode},
+    context::InputContext,
+    axis::{AxisInput, StickInput, PositionalInput, EulerAngleInput, RotationalInput},
+    order::{CascadeOrder, collect_cascade_edges},
+};
+
+
+pub struct CascadeDebugPlugin {
+    /// Context of the debug keys.
+    pub context: InputContext,
+}
+impl Plugin for CascadeDebugPlugin {
+    fn build(&self, app: &mut App) {
+        let context = self.context;
+        app
+            .init_resource::<CascadeDebugPanel>()
+            .add_systems(Startup, move |commands: Commands| spawn_cascade_debug_buttons(commands, context))
+            .add_systems(Update, (toggle_cascade_debug_panel, export_cascade_graph))
+            // capture before the events are cleared
+            .add_systems(PostUpdate, update_cascade_debug_panel.before(CascadeInputSet::Clear))
+        ;
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InputKind {
+    Button,
+    Axis,
+    Stick,
+    Positional,
+    EulerAngle,
+    Rotational,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CascadeGraphNode {
+    /// Entity formatted like `3v0`.
+    pub id: String,
+    pub name: Option<String>,
+    pub kind: InputKind,
+    pub value: String,
+    /// Events of this frame, only for buttons.
+    pub events: Vec<String>,
+    /// None for inputs in or after a cycle.
+    pub depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CascadeGraph {
+    /// Sorted by the depth, then the entity.
+    pub nodes: Vec<CascadeGraphNode>,
+    /// Pairs of the source id and the destination id.
+    pub edges: Vec<(String, String)>,
+}
+impl CascadeGraph {
+    pub fn capture(world: &mut World) -> Self {
+        let mut nodes: Vec<(Entity, InputKind, String, Vec<String>)> = Vec::new();
+        let mut buttons = world.query::<(Entity, &ButtonInput)>();
+        nodes.extend(buttons.iter(world).map(|(entity, button)| (
+            entity,
+            InputKind::Button,
+            if button.pressed() {"pressed"} else {"released"}.to_string(),
+            button.events().iter().map(|event| match event {
+                ButtonState::Pressed => "pressed".to_string(),
+                ButtonState::Released => "released".to_string(),
+            }).collect(),
+        )));
+        let mut axes = world.query::<(Entity, &AxisInput)>();
+        nodes.extend(axes.iter(world).map(|(entity, axis)| (entity, InputKind::Axis, format!("{:.3}", axis.value), Vec::new())));
+        let mut sticks = world.query::<(Entity, &StickInput)>();
+        nodes.extend(sticks.iter(world).map(|(entity, stick)| (entity, InputKind::Stick, format!("({:.3}, {:.3})", stick.value.x, stick.value.y), Vec::new())));
+        let mut positions = world.query::<(Entity, &PositionalInput)>();
+        nodes.extend(positions.iter(world).map(|(entity, position)| (
+            entity, InputKind::Positional,
+            format!("({:.3}, {:.3}, {:.3})", position.value.x, position.value.y, position.value.z),
+            Vec::new(),
+        )));
+        let mut eulers = world.query::<(Entity, &EulerAngleInput)>();
+        nodes.extend(eulers.iter(world).map(|(entity, euler)| (
+            entity, InputKind::EulerAngle,
+            format!("({:.3}, {:.3}, {:.3})", euler.value.x, euler.value.y, euler.value.z),
+            Vec::new(),
+        )));
+        let mut rotations = world.query::<(Entity, &RotationalInput)>();
+        nodes.extend(rotations.iter(world).map(|(entity, rotation)| {
+            let (y, x, z) = rotation.value.to_euler(EulerRot::YXZ);
+            (entity, InputKind::Rotational, format!("yxz({:.1}, {:.1}, {:.1}) deg", y.to_degrees(), x.to_degrees(), z.to_degrees()), Vec::new())
+        }));
+
+        let order = world.get_resource::<CascadeOrder>();
+        let mut nodes: Vec<(Entity, CascadeGraphNode)> = nodes.into_iter().map(|(entity, kind, value, events)| (entity, CascadeGraphNode {
+            id: format!("{:?}", entity),
+            name: world.get::<Name>(entity).map(|name| name.to_string()),
+            kind: kind,
+            value: value,
+            events: events,
+            depth: order.map_or(Some(0), |order| order.depth(entity)),
+        })).collect();
+        nodes.sort_by_key(|(entity, node)| (node.depth.unwrap_or(usize::MAX), *entity));
+
+        let mut edges = collect_cascade_edges(world);
+        edges.sort();
+        edges.dedup();
+        Self {
+            nodes: nodes.into_iter().map(|(_, node)| node).collect(),
+            edges: edges.into_iter().map(|(from, to)| (format!("{:?}", from), format!("{:?}", to))).collect(),
+        }
+    }
+
+    /// Graphviz DOT, flowing left to right.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cascade {\n    rankdir=LR;\n    node [shape=box];\n");
+        for node in self.nodes.iter() {
+            let mut label = match &node.name {
+                Some(name) => format!("{}\\n{:?}", escape(name), node.kind),
+                None => format!("{}\\n{:?}", node.id, node.kind),
+            };
+            let _ = write!(label, "\\n{}", escape(&node.value));
+            if !node.events.is_empty() {
+                let _ = write!(label, "\\n[{}]", node.events.join(", "));
+            }
+            let _ = writeln!(dot, "    \"{}\" [label=\"{}\"];", node.id, label);
+        }
+        for (from, to) in self.edges.iter() {
+            let _ = writeln!(dot, "    \"{}\" -> \"{}\";", from, to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+    /// One line per input, for the on-screen panel.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for node in self.nodes.iter() {
+            let depth = node.depth.map_or("cycle".to_string(), |depth| depth.to_string());
+            let name = node.name.clone().unwrap_or_else(|| node.id.clone());
+            let _ = write!(text, "{:>5} {:<20} {:<10} {}", depth, name, format!("{:?}", node.kind), node.value);
+            if !node.events.is_empty() {
+                let _ = write!(text, " [{}]", node.events.join(", "));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+/// Text of the panel, shown by the UI while `visible`.
+#[derive(Debug, Resource, Clone, Default)]
+pub struct CascadeDebugPanel {
+    pub visible: bool,
+    pub text: String,
+}
+
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq)]
+enum CascadeDebugAction {
+    TogglePanel,
+    ExportGraph,
+}
+
+fn spawn_cascade_debug_buttons(
+    mut commands: Commands,
+    context: InputContext,
+) {
+    for (name, key, action) in [
+        ("Debug Panel", KeyCode::F3, CascadeDebugAction::TogglePanel),
+        ("Debug Export", KeyCode::F9, CascadeDebugAction::ExportGraph),
+    ] {
+        commands.spawn((
+            Name::new(name),
+            context,
+            ButtonInput::default(),
+            MappedDeviceButton::new(DeviceButtonCode::Key(key)),
+            action,
+        ));
+    }
+}
+
+fn toggle_cascade_debug_panel(
+    buttons: Query<(&ButtonInput, &CascadeDebugAction)>,
+    mut panel: ResMut<CascadeDebugPanel>,
+) {
+    if buttons.iter().any(|(button, &action)| action == CascadeDebugAction::TogglePanel && button.just_pressed()) {
+        panel.visible = !panel.visible;
+    }
+}
+
+fn update_cascade_debug_panel(world: &mut World) {
+    if !world.resource::<CascadeDebugPanel>().visible {
+        return;
+    }
+    let text = CascadeGraph::capture(world).to_text();
+    world.resource_mut::<CascadeDebugPanel>().text = text;
+}
+
+fn export_cascade_graph(world: &mut World) {
+    let mut buttons = world.query::<(&ButtonInput, &CascadeDebugAction)>();
+    if !buttons.iter(world).any(|(button, &action)| action == CascadeDebugAction::ExportGraph && button.just_pressed()) {
+        return;
+    }
+    let graph = CascadeGraph::capture(world);
+    write_exports(&graph);
+}
+
+#[cfg(not(target_family="wasm"))]
+fn write_exports(graph: &CascadeGraph) {
+    for (path, text) in [("cascade.dot", graph.to_dot()), ("cascade.json", graph.to_json())] {
+        match std::fs::write(path, text) {
+            Ok(()) => info!("Exported cascade graph to {}", path),
+            Err(err) => warn!("Failed to export cascade graph to {}: {}", path, err),
+        }
+    }
+}
+
+#[cfg(target_family="wasm")]
+fn write_exports(graph: &CascadeGraph) {
+    info!("{}", graph.to_dot());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{InputPlugin, keyboard::KeyboardInput};
+
+    use super::*;
+    use crate::cascade_input::{
+        CascadeInputPlugin,
+        axis::{StickButtons, update_four_button_axis},
+        context::InputContextStack,
+        order::{CascadeAppExt, CascadeNodeSet},
+    };
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, bevy::window::WindowPlugin::default(), CascadeInputPlugin))
+            .add_cascade_node::<StickButtons, _>(CascadeNodeSet::Evaluate, update_four_button_axis);
+        app
+    }
+    fn key_event(key: KeyCode, state: ButtonState) -> KeyboardInput {
+        KeyboardInput { scan_code: 0, key_code: Some(key), state, window: Entity::PLACEHOLDER }
+    }
+    /// Runs up to the cascade, before the events of the frame are cleared.
+    fn update_inputs(app: &mut App) {
+        app.world.run_schedule(First);
+        app.world.run_schedule(PreUpdate);
+    }
+
+    #[test]
+    fn graph_snapshot() {
+        let mut app = app();
+        let mut button = |name: &str, key: KeyCode| app.world.spawn((
+            Name::new(name.to_string()),
+            ButtonInput::default(),
+            MappedDeviceButton::new(DeviceButtonCode::Key(key)),
+        )).id();
+        let buttons = [button("Left", KeyCode::A), button("Right", KeyCode::D), button("Back", KeyCode::S), button("Forward", KeyCode::W)];
+        app.world.spawn((
+            Name::new("Move"),
+            StickInput::default(),
+            StickButtons { negative_x: buttons[0], positive_x: buttons[1], negative_y: buttons[2], positive_y: buttons[3] },
+        ));
+        app.update();
+        app.world.send_event(key_event(KeyCode::W, ButtonState::Pressed));
+        update_inputs(&mut app);
+
+        let graph = CascadeGraph::capture(&mut app.world);
+        assert_eq!(graph.to_dot(), r#"digraph cascade {
+    rankdir=LR;
+    node [shape=box];
+    "1v0" [label="Left\nButton\nreleased"];
+    "2v0" [label="Right\nButton\nreleased"];
+    "3v0" [label="Back\nButton\nreleased"];
+    "4v0" [label="Forward\nButton\npressed\n[pressed]"];
+    "5v0" [label="Move\nStick\n(0.000, 1.000)"];
+    "1v0" -> "5v0";
+    "2v0" -> "5v0";
+    "3v0" -> "5v0";
+    "4v0" -> "5v0";
+}
+"#);
+        assert_eq!(graph.to_text(), r#"    0 Left                 Button     released
+    0 Right                Button     released
+    0 Back                 Button     released
+    0 Forward              Button     pressed [pressed]
+    1 Move                 Stick      (0.000, 1.000)
+"#);
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["nodes"][4]["name"], "Move");
+        assert_eq!(json["nodes"][4]["depth"], 1);
+        assert_eq!(json["edges"][3], serde_json::json!(["4v0", "5v0"]));
+    }
+
+    #[test]
+    fn debug_keys_go_through_contexts() {
+        const GAMEPLAY: InputContext = InputContext("gameplay");
+        const MENU: InputContext = InputContext("menu");
+        let mut app = app();
+        app
+            .insert_resource(InputContextStack::new(Vec::new()))
+            .add_plugins(CascadeDebugPlugin { context: GAMEPLAY });
+        app.world.resource_mut::<InputContextStack>().push(GAMEPLAY, false);
+        app.update();
+        let tap_f3 = |app: &mut App| {
+            app.world.send_event(key_event(KeyCode::F3, ButtonState::Pressed));
+            app.update();
+            app.world.send_event(key_event(KeyCode::F3, ButtonState::Released));
+            app.update();
+            app.world.resource::<CascadeDebugPanel>().visible
+        };
+        assert!(tap_f3(&mut app));
+        // blocked while typing in a menu
+        app.world.resource_mut::<InputContextStack>().push(MENU, true);
+        assert!(tap_f3(&mut app));
+        app.world.resource_mut::<InputContextStack>().remove(MENU);
+        assert!(!tap_f3(&mut app));
+    }
+}
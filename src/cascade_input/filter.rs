@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use super::{
+    axis::{StickInput, clamp_stick},
+    order::{CascadeAppExt, CascadeNodeSet, CascadeNode, CascadeOrder},
+};
+
+
+pub struct StickFilterPlugin;
+impl Plugin for StickFilterPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_cascade_systems(CascadeNodeSet::Filter, (
+                clamp_stick,
+                apply_scaled_dead_zones,
+                apply_response_curves,
+            ).chain())
+            .add_cascade_node::<LowPass, _>(CascadeNodeSet::Evaluate, update_low_pass_sticks)
+            .add_cascade_node::<RateLimit, _>(CascadeNodeSet::Evaluate, update_rate_limited_sticks)
+        ;
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeadZoneMode {
+    /// On the length, keeping the direction.
+    Radial,
+    /// On each axis separately, easier to move straight.
+    Axial,
+}
+
+/// Rescales `inner..outer` into `0..1`, so the value rises from zero at the edge without jump.
+/// Values beyond `outer` are full.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScaledDeadZone {
+    pub mode: DeadZoneMode,
+    pub inner: f32,
+    pub outer: f32,
+}
+impl ScaledDeadZone {
+    pub fn radial(inner: f32, outer: f32) -> Self {
+        Self { mode: DeadZoneMode::Radial, inner, outer }
+    }
+    pub fn axial(inner: f32, outer: f32) -> Self {
+        Self { mode: DeadZoneMode::Axial, inner, outer }
+    }
+    fn rescale(&self, length: f32) -> f32 {
+        ((length - self.inner) / (self.outer - self.inner).max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+    pub fn apply(&self, value: Vec2) -> Vec2 {
+        match self.mode {
+            DeadZoneMode::Radial => {
+                let length = value.length();
+                if length == 0.0 {
+                    return Vec2::ZERO;
+                }
+                value * (self.rescale(length) / length)
+            },
+            DeadZoneMode::Axial => Vec2::new(
+                value.x.signum() * self.rescale(value.x.abs()),
+                value.y.signum() * self.rescale(value.y.abs()),
+            ),
+        }
+    }
+}
+pub fn apply_scaled_dead_zones(
+    mut sticks: Query<(Entity, &mut StickInput, &ScaledDeadZone)>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, dead_zone) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let value = dead_zone.apply(stick.value);
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+
+/// Maps the length of the stick, keeping the direction.
+/// Curves pass `(0, 0)` and `(1, 1)`, so that only the feel around the center changes.
+#[derive(Debug, Component, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ResponseCurve {
+    Linear,
+    /// `(e^(k x) - 1) / (e^k - 1)`, slower around the center for positive `k`.
+    Exponential(f32),
+    /// `x^p`, slower around the center for `p` larger than 1.
+    Power(f32),
+    /// Cubic spline through the points, between the implicit `(0, 0)` and `(1, 1)`.
+    /// Extrapolated linearly beyond `1`, like for mouse.
+    Spline(Vec<Vec2>),
+}
+impl ResponseCurve {
+    pub fn evaluate(&self, x: f32) -> f32 {
+        match self {
+            Self::Linear => x,
+            Self::Exponential(k) => {
+                if k.abs() < f32::EPSILON {
+                    return x;
+                }
+                ((k * x).exp() - 1.0) / (k.exp() - 1.0)
+            },
+            Self::Power(p) => x.powf(*p),
+            Self::Spline(points) => {
+                let mut knots = Vec::with_capacity(points.len() + 2);
+                knots.push(Vec2::ZERO);
+                knots.extend(points.iter().copied().filter(|point| 0.0 < point.x && point.x < 1.0));
+                knots.push(Vec2::ONE);
+                knots.sort_by(|a, b| a.x.total_cmp(&b.x));
+                evaluate_spline(&knots, x)
+            },
+        }
+    }
+    pub fn apply(&self, value: Vec2) -> Vec2 {
+        let length = value.length();
+        if length == 0.0 {
+            return Vec2::ZERO;
+        }
+        value * (self.evaluate(length).max(0.0) / length)
+    }
+}
+/// Cubic Hermite spline with Catmull-Rom tangents over sorted knots.
+fn evaluate_spline(knots: &[Vec2], x: f32) -> f32 {
+    let last = knots.len() - 1;
+    let slope = |i: usize, j: usize| (knots[j].y - knots[i].y) / (knots[j].x - knots[i].x).max(f32::EPSILON);
+    if knots[last].x <= x {
+        return knots[last].y + slope(last - 1, last) * (x - knots[last].x);
+    }
+    let i = knots.iter().rposition(|knot| knot.x <= x).unwrap_or(0).min(last - 1);
+    let tangent = |k: usize| slope(k.saturating_sub(1), (k + 1).min(last));
+    let (p0, p1) = (knots[i], knots[i + 1]);
+    let width = p1.x - p0.x;
+    let t = ((x - p0.x) / width.max(f32::EPSILON)).clamp(0.0, 1.0);
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0.y
+        + (t3 - 2.0 * t2 + t) * width * tangent(i)
+        + (-2.0 * t3 + 3.0 * t2) * p1.y
+        + (t3 - t2) * width * tangent(i + 1)
+}
+pub fn apply_response_curves(
+    mut sticks: Query<(Entity, &mut StickInput, &ResponseCurve)>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, curve) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let value = curve.apply(stick.value);
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+
+/// Follows the source smoothly, reaching 63% of a step in `time_constant` seconds.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct LowPass {
+    pub source: Entity,
+    pub time_constant: f32,
+}
+impl CascadeNode for LowPass {
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+fn update_low_pass_sticks(
+    mut sticks: Query<(Entity, &mut StickInput, &LowPass)>,
+    sources: Query<&StickInput, Without<LowPass>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, low_pass) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = sources.get(low_pass.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        // exact for any frame time, unlike a fixed blend factor
+        let blend = if 0.0 < low_pass.time_constant {
+            1.0 - (-time.delta_seconds() / low_pass.time_constant).exp()
+        } else {
+            1.0
+        };
+        let value = stick.value.lerp(source.value, blend);
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+/// Follows the source, moving at most `max_rate` per second.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub source: Entity,
+    pub max_rate: f32,
+}
+impl CascadeNode for RateLimit {
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+fn update_rate_limited_sticks(
+    mut sticks: Query<(Entity, &mut StickInput, &RateLimit)>,
+    sources: Query<&StickInput, Without<RateLimit>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, rate_limit) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = sources.get(rate_limit.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        let step = (source.value - stick.value).clamp_length_max(rate_limit.max_rate * time.delta_seconds());
+        let value = stick.value + step;
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
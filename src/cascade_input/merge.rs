@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+
+use super::{
+    button::ButtonInput,
+    axis::{StickInput, RotationalInput},
+    order::{CascadeAppExt, CascadeNodeSet, CascadeNode, CascadeOrder},
+};
+
+
+pub struct MergeInputPlugin;
+impl Plugin for MergeInputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_cascade_node::<ButtonAny, _>(CascadeNodeSet::Evaluate, update_button_any)
+            .add_cascade_node::<StickSum, _>(CascadeNodeSet::Evaluate, update_stick_sum)
+            .add_cascade_node::<StickMaxMagnitude, _>(CascadeNodeSet::Evaluate, update_stick_max_magnitude)
+            .add_cascade_node::<RotationCompose, _>(CascadeNodeSet::Evaluate, update_rotation_compose)
+        ;
+    }
+}
+
+
+/// Pressed while any of the sources is pressed.
+#[derive(Debug, Component, Clone, PartialEq)]
+pub struct ButtonAny {
+    pub sources: Vec<Entity>,
+}
+impl CascadeNode for ButtonAny {
+    fn sources(&self) -> Vec<Entity> { self.sources.clone() }
+}
+fn update_button_any(
+    mut buttons: Query<(Entity, &mut ButtonInput, &ButtonAny)>,
+    sources: Query<&ButtonInput, Without<ButtonAny>>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut button, any) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(pressed) = any.sources.iter().map(|&source| sources.get(source).map(|s| s.pressed())).collect::<Result<Vec<_>, _>>() else {
+            warn!("Entity not found");
+            continue;
+        };
+        let pressed = pressed.into_iter().any(|p| p);
+        // avoid false change detection
+        if button.pressed() != pressed {
+            if pressed { button.press(); } else { button.release(); }
+        }
+    }
+}
+
+
+/// Sum of the sources, clamp it with `MaxLength` if needed.
+#[derive(Debug, Component, Clone, PartialEq)]
+pub struct StickSum {
+    pub sources: Vec<Entity>,
+}
+impl CascadeNode for StickSum {
+    fn sources(&self) -> Vec<Entity> { self.sources.clone() }
+}
+fn update_stick_sum(
+    mut sticks: Query<(Entity, &mut StickInput, &StickSum)>,
+    sources: Query<&StickInput, Without<StickSum>>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, sum) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(values) = sum.sources.iter().map(|&source| sources.get(source).map(|s| s.value)).collect::<Result<Vec<_>, _>>() else {
+            warn!("Entity not found");
+            continue;
+        };
+        let value = values.into_iter().sum::<Vec2>();
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+/// The longest of the sources, so devices never add up beyond full tilt.
+#[derive(Debug, Component, Clone, PartialEq)]
+pub struct StickMaxMagnitude {
+    pub sources: Vec<Entity>,
+}
+impl CascadeNode for StickMaxMagnitude {
+    fn sources(&self) -> Vec<Entity> { self.sources.clone() }
+}
+fn update_stick_max_magnitude(
+    mut sticks: Query<(Entity, &mut StickInput, &StickMaxMagnitude)>,
+    sources: Query<&StickInput, Without<StickMaxMagnitude>>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, max) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(values) = max.sources.iter().map(|&source| sources.get(source).map(|s| s.value)).collect::<Result<Vec<_>, _>>() else {
+            warn!("Entity not found");
+            continue;
+        };
+        let value = values.into_iter().reduce(|v1, v2| if v1.length_squared() < v2.length_squared() {v2} else {v1}).unwrap_or_default();
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+
+/// Product of the sources in order, the first applied last.
+/// Put the player rotation first and assists like recoil after, to add to it without replacing.
+#[derive(Debug, Component, Clone, PartialEq)]
+pub struct RotationCompose {
+    pub sources: Vec<Entity>,
+}
+impl CascadeNode for RotationCompose {
+    fn sources(&self) -> Vec<Entity> { self.sources.clone() }
+}
+fn update_rotation_compose(
+    mut rotations: Query<(Entity, &mut RotationalInput, &RotationCompose)>,
+    sources: Query<&RotationalInput, Without<RotationCompose>>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut rotation, compose) in rotations.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(values) = compose.sources.iter().map(|&source| sources.get(source).map(|s| s.value)).collect::<Result<Vec<_>, _>>() else {
+            warn!("Entity not found");
+            continue;
+        };
+        let value = values.into_iter().fold(Quat::IDENTITY, |composed, value| composed * value).normalize();
+        // avoid false change detection
+        if rotation.value != value {
+            rotation.value = value;
+        }
+    }
+}
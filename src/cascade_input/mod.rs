@@ -8,37 +8,80 @@
 //! Virtual gamepad will be set of reference to their inputs,
 //! which may be child of the gamepad for recursive despawning.
 //!
-//! Remember cascade systems need to be explicit ordered.
+//! Cascade systems are ordered by the references between inputs, see [`order`].
+//!
+//! Inputs are sampled once per frame, and button events live until [`CascadeInputSet::Clear`],
+//! after the state machines transition on PostUpdate.
+//! Every consumer, the state machines, locomotion, firing and the physics, runs once per frame,
+//! so there is no sampling mode for `FixedUpdate`: seldom_state runs its machines only on PostUpdate,
+//! where triggers could not read a snapshot latched for fixed ticks anyway.
+//! A system on `FixedUpdate` is to latch the events it needs by itself, or to be moved to `Update`.
 
-use bevy::prelude::*;
+use bevy::{prelude::*, input::InputSystem};
 use self::{
     button::ButtonInputPlugin,
     axis::AxisInputPlugin,
+    record::InputRecordPlugin,
+    context::InputContextStack,
+    order::CascadeOrderPlugin,
+    filter::StickFilterPlugin,
+    convert::ConvertInputPlugin,
+    merge::MergeInputPlugin,
+    buffer::BufferedPressPlugin,
 };
 
 /// Deals with boolean inputs.
 pub mod button;
 /// Deals with vector inputs.
 pub mod axis;
+/// Derives buttons from timing of other buttons.
+pub mod timing;
+/// Combines buttons into other buttons.
+pub mod chord;
+/// Detects sequences of inputs.
+pub mod combo;
+/// Shapes sticks, such as dead zones, response curves and smoothing.
+pub mod filter;
+/// Converts sticks and axes into buttons, and buttons into sticks.
+pub mod convert;
+/// Merges several inputs into one, to drive an action from several devices.
+pub mod merge;
+/// Dumps the cascade for debugging.
+pub mod debug;
+/// Keeps presses pending until a state machine consumes them.
+pub mod buffer;
+/// Records and replays device-mapped inputs.
+pub mod record;
+/// Scopes device-mapped inputs into stackable contexts.
+pub mod context;
+/// Orders cascade systems by the references between inputs.
+pub mod order;
 
 pub struct CascadeInputPlugin;
 impl Plugin for CascadeInputPlugin {
     fn build(&self, app: &mut App) {
         app
+            .configure_set(PreUpdate, CascadeInputSet::Begin.after(InputSystem))
             .configure_set(PreUpdate, CascadeInputSet::Flush.after(CascadeInputSet::Begin))
             .configure_set(PreUpdate, CascadeInputSet::DeviceMappedInputs.in_set(CascadeInputSet::Flush))
+            .configure_set(PreUpdate, CascadeInputSet::Cascade.in_set(CascadeInputSet::Flush).after(CascadeInputSet::DeviceMappedInputs))
             .configure_set(PostUpdate, CascadeInputSet::Clear.after(seldom_state::set::StateSet::Transition))
-            .add_plugins((ButtonInputPlugin, AxisInputPlugin, ))
+            .init_resource::<InputContextStack>()
+            .add_plugins((ButtonInputPlugin, AxisInputPlugin, InputRecordPlugin, CascadeOrderPlugin, StickFilterPlugin, ConvertInputPlugin, MergeInputPlugin, BufferedPressPlugin, ))
         ;
     }
 }
 
 /// SystemSet which this module use.
-/// Your systems to update inputs will be in set of Flush, on PreUpdate stage.
+/// Your systems to update inputs will be in set of Flush, on PreUpdate stage,
+/// or in [`order::CascadeSchedule`] to be ordered automatically.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum CascadeInputSet {
     Begin,
     Flush,
     DeviceMappedInputs,
+    /// Runs [`order::CascadeSchedule`] on the device-mapped inputs.
+    Cascade,
+    /// Clears button events of the frame, on PostUpdate stage.
     Clear,
 }
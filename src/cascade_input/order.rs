@@ -0,0 +1,318 @@
+//! Orders cascade systems by the references between inputs.
+//!
+//! Each node component tells which inputs it reads and writes by [`CascadeNode`].
+//! Inputs are sorted topologically every frame, then [`CascadeSchedule`] runs once per depth,
+//! and each system evaluates only the inputs of the current depth, checked by [`CascadeOrder::is_current`].
+//! Inputs without references are on depth 0, right after device-mapped inputs.
+//!
+//! Since the systems run once per depth, `Changed` filters on the sources miss changes made on earlier depths,
+//! so systems evaluate their inputs every frame on the current depth instead.
+//! A node closing a cycle is rejected when it is added, removing the node component with an error.
+
+use std::{any::TypeId, collections::{HashMap, HashSet}};
+
+use bevy::{prelude::*, ecs::schedule::{ScheduleLabel, SystemConfigs}};
+
+use super::CascadeInputSet;
+
+
+pub struct CascadeOrderPlugin;
+impl Plugin for CascadeOrderPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_schedule(CascadeSchedule)
+            .configure_sets(CascadeSchedule, (
+                CascadeNodeSet::Evaluate,
+                CascadeNodeSet::Filter,
+                CascadeNodeSet::Propagate,
+            ).chain())
+            .init_resource::<CascadeOrder>()
+            .init_resource::<CascadeRegistry>()
+            .add_systems(PreUpdate,
+                run_cascade
+                .in_set(CascadeInputSet::Cascade)
+            )
+        ;
+    }
+}
+
+/// Runs once per depth of the cascade, inside [`CascadeInputSet::Cascade`].
+#[derive(ScheduleLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CascadeSchedule;
+
+/// Stages of evaluating the inputs on the same depth.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum CascadeNodeSet {
+    /// Computes the value from the sources.
+    Evaluate,
+    /// Modifies the own value in place, like clamping.
+    /// Runs every frame, so the value is to be evaluated again every frame, not to be filtered twice.
+    Filter,
+    /// Writes the value into other inputs.
+    Propagate,
+}
+
+/// Component referring other inputs.
+pub trait CascadeNode: Component {
+    /// Inputs read to update the entity holding this.
+    fn sources(&self) -> Vec<Entity> { Vec::new() }
+    /// Inputs written other than the entity holding this.
+    fn targets(&self) -> Vec<Entity> { Vec::new() }
+}
+
+pub trait CascadeAppExt {
+    /// Registers the references of the node, and adds the systems into the stage.
+    fn add_cascade_node<N: CascadeNode, M>(&mut self, set: CascadeNodeSet, systems: impl IntoSystemConfigs<M>) -> &mut Self;
+    /// Adds the systems into the stage, for components without references.
+    fn add_cascade_systems<M>(&mut self, set: CascadeNodeSet, systems: impl IntoSystemConfigs<M>) -> &mut Self;
+}
+impl CascadeAppExt for App {
+    fn add_cascade_node<N: CascadeNode, M>(&mut self, set: CascadeNodeSet, systems: impl IntoSystemConfigs<M>) -> &mut Self {
+        self.world.get_resource_or_insert_with(CascadeRegistry::default).register::<N>();
+        self.add_cascade_systems(set, systems)
+    }
+    fn add_cascade_systems<M>(&mut self, set: CascadeNodeSet, systems: impl IntoSystemConfigs<M>) -> &mut Self {
+        let systems: SystemConfigs = systems.into_configs();
+        self.add_systems(CascadeSchedule, systems.in_set(set))
+    }
+}
+
+/// Nodes of a registered type, each with its references.
+type EdgeCollector = fn(&mut World, &mut Vec<(Entity, Vec<(Entity, Entity)>)>);
+/// Removes the node component of a registered type.
+type NodeRejector = fn(&mut World, Entity);
+
+#[derive(Resource, Default)]
+struct CascadeRegistry {
+    types: HashSet<TypeId>,
+    kinds: Vec<(EdgeCollector, NodeRejector)>,
+    /// Nodes checked not to close a cycle, by the index of the kind.
+    accepted: HashSet<(usize, Entity)>,
+}
+impl CascadeRegistry {
+    fn register<N: CascadeNode>(&mut self) {
+        if self.types.insert(TypeId::of::<N>()) {
+            self.kinds.push((collect_edges::<N>, reject_node::<N>));
+        }
+    }
+}
+fn collect_edges<N: CascadeNode>(world: &mut World, nodes: &mut Vec<(Entity, Vec<(Entity, Entity)>)>) {
+    let mut query = world.query::<(Entity, &N)>();
+    for (entity, node) in query.iter(world) {
+        let edges = node.sources().into_iter().map(|source| (source, entity))
+            .chain(node.targets().into_iter().map(|target| (entity, target)))
+            .collect();
+        nodes.push((entity, edges));
+    }
+}
+fn reject_node<N: CascadeNode>(world: &mut World, entity: Entity) {
+    world.entity_mut(entity).remove::<N>();
+}
+
+/// Depths of the inputs in the cascade.
+#[derive(Resource, Debug, Default)]
+pub struct CascadeOrder {
+    depths: HashMap<Entity, usize>,
+    blocked: HashSet<Entity>,
+    max_depth: usize,
+    current: usize,
+}
+impl CascadeOrder {
+    /// Longest path from the inputs without sources, or None for inputs in or after a cycle.
+    pub fn depth(&self, entity: Entity) -> Option<usize> {
+        if self.blocked.contains(&entity) {
+            return None;
+        }
+        Some(self.depths.get(&entity).copied().unwrap_or(0))
+    }
+    /// Whether the input is to be evaluated on this run of [`CascadeSchedule`].
+    pub fn is_current(&self, entity: Entity) -> bool {
+        self.depth(entity) == Some(self.current)
+    }
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn sort(&mut self, edges: &[(Entity, Entity)]) {
+        let mut successors: HashMap<Entity, Vec<Entity>> = HashMap::new();
+        let mut in_degrees: HashMap<Entity, usize> = HashMap::new();
+        for &(from, to) in edges {
+            successors.entry(from).or_default().push(to);
+            in_degrees.entry(from).or_default();
+            *in_degrees.entry(to).or_default() += 1;
+        }
+        let mut depths: HashMap<Entity, usize> = HashMap::new();
+        let mut queue: Vec<Entity> = in_degrees.iter().filter(|(_, &degree)| degree == 0).map(|(&entity, _)| entity).collect();
+        while let Some(entity) = queue.pop() {
+            let depth = *depths.entry(entity).or_default();
+            for &next in successors.get(&entity).into_iter().flatten() {
+                let next_depth = depths.entry(next).or_default();
+                *next_depth = (*next_depth).max(depth + 1);
+                let degree = in_degrees.get_mut(&next).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push(next);
+                }
+            }
+        }
+        // never reached zero in-degree
+        self.blocked = in_degrees.into_iter().filter(|(_, degree)| 0 < *degree).map(|(entity, _)| entity).collect();
+        depths.retain(|entity, _| !self.blocked.contains(entity));
+        self.max_depth = depths.values().copied().max().unwrap_or(0);
+        self.depths = depths;
+    }
+}
+
+/// References of every registered node, as pairs of the source and the destination.
+pub fn collect_cascade_edges(world: &mut World) -> Vec<(Entity, Entity)> {
+    let kinds = world.get_resource::<CascadeRegistry>().map(|registry| registry.kinds.clone()).unwrap_or_default();
+    let mut nodes = Vec::new();
+    for (collect, _) in kinds {
+        collect(world, &mut nodes);
+    }
+    nodes.into_iter().flat_map(|(_, edges)| edges).collect()
+}
+
+/// Accepts the nodes added since the last frame one by one, rejecting those closing a cycle.
+/// Returns the references of the accepted nodes.
+fn accept_cascade_nodes(world: &mut World) -> Vec<(Entity, Entity)> {
+    let Some(kinds) = world.get_resource::<CascadeRegistry>().map(|registry| registry.kinds.clone()) else {
+        return Vec::new();
+    };
+    let mut present = HashSet::new();
+    let mut edges = Vec::new();
+    let mut added = Vec::new();
+    for (kind, (collect, _)) in kinds.iter().enumerate() {
+        let mut nodes = Vec::new();
+        collect(world, &mut nodes);
+        let registry = world.resource::<CascadeRegistry>();
+        for (entity, node_edges) in nodes {
+            present.insert((kind, entity));
+            if registry.accepted.contains(&(kind, entity)) {
+                edges.extend(node_edges);
+            } else {
+                added.push((kind, entity, node_edges));
+            }
+        }
+    }
+    let mut registry = world.resource_mut::<CascadeRegistry>();
+    registry.accepted.retain(|node| present.contains(node));
+    if added.is_empty() {
+        return edges;
+    }
+
+    let mut order = CascadeOrder::default();
+    order.sort(&edges);
+    for (kind, entity, node_edges) in added {
+        let blocked_before = order.blocked.clone();
+        let accepted_len = edges.len();
+        edges.extend(node_edges);
+        order.sort(&edges);
+        // cycles made by changing accepted nodes are left to the check of every frame
+        if order.blocked.is_subset(&blocked_before) {
+            world.resource_mut::<CascadeRegistry>().accepted.insert((kind, entity));
+            continue;
+        }
+        let name = world.get::<Name>(entity).map_or(format!("{:?}", entity), |name| name.to_string());
+        error!("Cascade node on {} is rejected, closing a cycle.", name);
+        edges.truncate(accepted_len);
+        order.sort(&edges);
+        (kinds[kind].1)(world, entity);
+    }
+    edges
+}
+
+fn run_cascade(world: &mut World) {
+    let edges = accept_cascade_nodes(world);
+    let mut order = world.resource_mut::<CascadeOrder>();
+    let blocked_before = order.blocked.clone();
+    order.sort(&edges);
+    let blocked = order.blocked.clone();
+    // report once, not every frame
+    if blocked != blocked_before && !blocked.is_empty() {
+        let names: Vec<String> = blocked.iter()
+            .map(|&entity| world.get::<Name>(entity).map_or(format!("{:?}", entity), |name| name.to_string()))
+            .collect();
+        error!("Cascade inputs in or after a cycle are not evaluated: {:?}", names);
+    }
+
+    let max_depth = world.resource::<CascadeOrder>().max_depth;
+    for depth in 0..=max_depth {
+        world.resource_mut::<CascadeOrder>().current = depth;
+        world.run_schedule(CascadeSchedule);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::{input::InputPlugin, window::WindowPlugin};
+
+    use super::*;
+    use crate::cascade_input::CascadeInputPlugin;
+
+    /// Reads the other input.
+    #[derive(Component)]
+    struct Follow(Entity);
+    impl CascadeNode for Follow {
+        fn sources(&self) -> Vec<Entity> { vec![self.0] }
+    }
+
+    /// Depths each follower was evaluated at.
+    #[derive(Resource, Default)]
+    struct Evaluated(Vec<(Entity, usize)>);
+
+    fn evaluate_followers(
+        followers: Query<Entity, With<Follow>>,
+        order: Res<CascadeOrder>,
+        mut evaluated: ResMut<Evaluated>,
+    ) {
+        for entity in followers.iter() {
+            if !order.is_current(entity) {continue;}
+            evaluated.0.push((entity, order.current));
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+            .init_resource::<Evaluated>()
+            .add_cascade_node::<Follow, _>(CascadeNodeSet::Evaluate, evaluate_followers);
+        app
+    }
+
+    #[test]
+    fn chains_are_evaluated_by_depth() {
+        let mut app = app();
+        let root = app.world.spawn_empty().id();
+        // spawned in reverse, so that the order comes from the references
+        let third = app.world.spawn_empty().id();
+        let second = app.world.spawn_empty().id();
+        let first = app.world.spawn(Follow(root)).id();
+        app.world.entity_mut(second).insert(Follow(first));
+        app.world.entity_mut(third).insert(Follow(second));
+        app.update();
+
+        let order = app.world.resource::<CascadeOrder>();
+        assert_eq!([root, first, second, third].map(|entity| order.depth(entity)), [Some(0), Some(1), Some(2), Some(3)]);
+        assert_eq!(order.max_depth(), 3);
+        assert_eq!(app.world.resource::<Evaluated>().0, vec![(first, 1), (second, 2), (third, 3)]);
+    }
+
+    #[test]
+    fn nodes_closing_a_cycle_are_rejected() {
+        let mut app = app();
+        let first = app.world.spawn_empty().id();
+        let second = app.world.spawn(Follow(first)).id();
+        let third = app.world.spawn(Follow(second)).id();
+        app.update();
+
+        app.world.entity_mut(first).insert(Follow(third));
+        app.update();
+        assert!(app.world.get::<Follow>(first).is_none());
+        assert!(app.world.get::<Follow>(third).is_some());
+        let order = app.world.resource::<CascadeOrder>();
+        assert_eq!([first, second, third].map(|entity| order.depth(entity)), [Some(0), Some(1), Some(2)]);
+    }
+}
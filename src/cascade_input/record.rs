@@ -0,0 +1,318 @@
+//! Records device-mapped inputs per frame, and replays them in place of devices.
+//!
+//! Inputs are identified by their [`Name`], so the replaying world must spawn the same named inputs.
+//! Values are recorded every frame as the devices wrote them, before the cascade filters them in place,
+//! so that replaying goes through the same filters once.
+//! Frame durations are recorded too, and replayed through [`TimeUpdateStrategy`].
+
+use std::{fmt, path::{Path, PathBuf}, time::Duration, collections::HashMap};
+
+use bevy::{
+    prelude::*,
+    input::ButtonState,
+    app::AppExit,
+    time::{TimeUpdateStrategy, TimeSystem},
+};
+use serde::{Serialize, Deserialize};
+
+use super::{
+    CascadeInputSet,
+    button::{ButtonInput, MappedDeviceButton},
+    axis::{StickInput, AxisInput, MappedMouse, MappedWheel, MappedGamepadStick, MappedGamepadAxis},
+};
+
+
+pub struct InputRecordPlugin;
+impl Plugin for InputRecordPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(First,
+                start_replay
+                .before(TimeSystem)
+                .run_if(resource_added::<InputReplay>())
+            )
+            .add_systems(PreUpdate,
+                replay_inputs
+                .in_set(CascadeInputSet::DeviceMappedInputs)
+                .run_if(resource_exists::<InputReplay>())
+            )
+            .add_systems(PreUpdate,
+                record_inputs
+                .in_set(CascadeInputSet::Flush)
+                .after(CascadeInputSet::DeviceMappedInputs)
+                .before(CascadeInputSet::Cascade)
+                .run_if(resource_exists::<InputRecorder>())
+            )
+            .add_systems(Last, save_recording_on_exit.run_if(resource_exists::<InputRecorder>()))
+        ;
+    }
+}
+
+
+/// Device-mapped inputs in one frame.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Duration of the frame in seconds.
+    pub delta: f32,
+    /// Index of the track and its button events in order.
+    pub buttons: Vec<(u16, Vec<bool>)>,
+    pub sticks: Vec<(u16, Vec2)>,
+    pub axes: Vec<(u16, f32)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    /// Names of the recorded inputs, indexed from frames.
+    pub tracks: Vec<String>,
+    pub frames: Vec<RecordedFrame>,
+}
+impl InputRecording {
+    fn track(&mut self, name: &str) -> u16 {
+        if let Some(index) = self.tracks.iter().position(|track| track == name) {
+            return index as u16;
+        }
+        self.tracks.push(name.to_string());
+        (self.tracks.len() - 1) as u16
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        let bytes = std::fs::read(path).map_err(RecordingError::Io)?;
+        bincode::deserialize(&bytes).map_err(RecordingError::Encoding)
+    }
+    pub fn save(&self, path: &Path) -> Result<(), RecordingError> {
+        let bytes = bincode::serialize(self).map_err(RecordingError::Encoding)?;
+        std::fs::write(path, bytes).map_err(RecordingError::Io)
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+}
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Encoding(err) => write!(f, "{}", err),
+        }
+    }
+}
+impl std::error::Error for RecordingError {}
+
+
+/// Records while exists. Saved to the path on [`AppExit`] if any.
+#[derive(Debug, Resource, Default)]
+pub struct InputRecorder {
+    pub recording: InputRecording,
+    pub path: Option<PathBuf>,
+}
+impl InputRecorder {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { recording: InputRecording::default(), path }
+    }
+}
+
+/// Replays while exists, in place of device-mapped systems.
+/// Removed after the last frame.
+#[derive(Debug, Resource)]
+pub struct InputReplay {
+    pub recording: InputRecording,
+    cursor: usize,
+}
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        Self { recording, cursor: 0 }
+    }
+    pub fn is_finished(&self) -> bool { self.recording.frames.len() <= self.cursor }
+}
+
+/// Run condition for device-mapped systems.
+pub fn replaying(replay: Option<Res<InputReplay>>) -> bool {
+    replay.is_some()
+}
+
+
+fn record_inputs(
+    mut recorder: ResMut<InputRecorder>,
+    buttons: Query<(&Name, &ButtonInput), With<MappedDeviceButton>>,
+    sticks: Query<(&Name, &StickInput), Or<(With<MappedMouse>, With<MappedWheel>, With<MappedGamepadStick>)>>,
+    axes: Query<(&Name, &AxisInput), Or<(With<MappedGamepadAxis>, With<MappedWheel>)>>,
+    time: Res<Time>,
+) {
+    let recording = &mut recorder.recording;
+    let mut frame = RecordedFrame {
+        delta: time.delta_seconds(),
+        ..default()
+    };
+    for (name, button) in buttons.iter() {
+        let events = button.events();
+        if events.is_empty() {
+            continue;
+        }
+        let track = recording.track(name.as_str());
+        frame.buttons.push((track, events.iter().map(|&state| state == ButtonState::Pressed).collect()));
+    }
+    for (name, stick) in sticks.iter() {
+        let track = recording.track(name.as_str());
+        frame.sticks.push((track, stick.value));
+    }
+    for (name, axis) in axes.iter() {
+        let track = recording.track(name.as_str());
+        frame.axes.push((track, axis.value));
+    }
+    // empty frames are kept to replay the frame timing
+    recording.frames.push(frame);
+}
+
+/// Times the first replayed frame, later frames are timed by the previous ones.
+fn start_replay(
+    replay: Res<InputReplay>,
+    mut time_update: ResMut<TimeUpdateStrategy>,
+) {
+    if let Some(frame) = replay.recording.frames.get(replay.cursor) {
+        *time_update = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(frame.delta));
+    }
+}
+
+fn replay_inputs(
+    mut commands: Commands,
+    mut replay: ResMut<InputReplay>,
+    mut buttons: Query<(&Name, &mut ButtonInput), With<MappedDeviceButton>>,
+    mut sticks: Query<(&Name, &mut StickInput), Or<(With<MappedMouse>, With<MappedWheel>, With<MappedGamepadStick>)>>,
+    mut axes: Query<(&Name, &mut AxisInput), Or<(With<MappedGamepadAxis>, With<MappedWheel>)>>,
+    mut time_update: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(frame) = replay.recording.frames.get(replay.cursor) else {
+        info!("Input replay finished.");
+        *time_update = TimeUpdateStrategy::Automatic;
+        commands.remove_resource::<InputReplay>();
+        return;
+    };
+    let tracks: HashMap<&str, u16> = replay.recording.tracks.iter().enumerate()
+        .map(|(index, name)| (name.as_str(), index as u16))
+        .collect();
+    for (name, mut button) in buttons.iter_mut() {
+        let Some(track) = tracks.get(name.as_str()) else {continue;};
+        for (_, events) in frame.buttons.iter().filter(|(index, _)| index == track) {
+            for &pressed in events.iter() {
+                button.set(if pressed {ButtonState::Pressed} else {ButtonState::Released});
+            }
+        }
+    }
+    for (name, mut stick) in sticks.iter_mut() {
+        let Some(track) = tracks.get(name.as_str()) else {continue;};
+        if let Some(&(_, value)) = frame.sticks.iter().find(|(index, _)| index == track) {
+            stick.value = value;
+        }
+    }
+    for (name, mut axis) in axes.iter_mut() {
+        let Some(track) = tracks.get(name.as_str()) else {continue;};
+        if let Some(&(_, value)) = frame.axes.iter().find(|(index, _)| index == track) {
+            axis.value = value;
+        }
+    }
+    // time of the next frame
+    if let Some(next) = replay.recording.frames.get(replay.cursor + 1) {
+        *time_update = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(next.delta));
+    }
+    replay.cursor += 1;
+}
+
+fn save_recording_on_exit(
+    recorder: Res<InputRecorder>,
+    mut exit_events: EventReader<AppExit>,
+) {
+    if exit_events.iter().next().is_none() {
+        return;
+    }
+    let Some(path) = &recorder.path else {
+        return;
+    };
+    match recorder.recording.save(path) {
+        Ok(()) => info!("Input recording saved to {:?}", path),
+        Err(err) => warn!("Failed to save input recording {:?}: {}", path, err),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        input::{InputPlugin, keyboard::KeyboardInput, mouse::MouseMotion},
+        window::WindowPlugin,
+    };
+
+    use super::*;
+    use crate::cascade_input::{CascadeInputPlugin, button::DeviceButtonCode, axis::MappedMouse};
+
+    /// Time, button events and stick value of each frame, as the game sees them.
+    #[derive(Resource, Default)]
+    struct Observed(Vec<(f32, Vec<ButtonState>, Vec2)>);
+
+    fn observe(
+        mut observed: ResMut<Observed>,
+        button: Query<&ButtonInput>,
+        stick: Query<&StickInput>,
+        time: Res<Time>,
+    ) {
+        observed.0.push((time.delta_seconds(), button.single().events(), stick.single().value));
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+            .init_resource::<Observed>()
+            .add_systems(Update, observe);
+        app.world.spawn((Name::new("Jump"), ButtonInput::default(), MappedDeviceButton::new(DeviceButtonCode::Key(KeyCode::Space))));
+        app.world.spawn((Name::new("Look"), StickInput::default(), MappedMouse::new(Vec2::splat(0.01))));
+        // the first frame has no duration
+        app.update();
+        app.world.resource_mut::<Observed>().0.clear();
+        app
+    }
+
+    #[test]
+    fn replay_reproduces_the_recorded_frames() {
+        let mut recording_app = app();
+        recording_app.insert_resource(InputRecorder::new(None));
+        let key = |state| KeyboardInput { scan_code: 0, key_code: Some(KeyCode::Space), state, window: Entity::PLACEHOLDER };
+        for frame in 0..8 {
+            match frame {
+                // tapped within a frame
+                1 => {
+                    recording_app.world.send_event(key(ButtonState::Pressed));
+                    recording_app.world.send_event(key(ButtonState::Released));
+                },
+                3 => recording_app.world.send_event(key(ButtonState::Pressed)),
+                6 => recording_app.world.send_event(key(ButtonState::Released)),
+                _ => {},
+            }
+            if frame < 4 {
+                recording_app.world.send_event(MouseMotion { delta: Vec2::new(frame as f32, -2.0) });
+            }
+            let delta = Duration::from_millis(10 + 5 * frame);
+            recording_app.insert_resource(TimeUpdateStrategy::ManualDuration(delta));
+            recording_app.update();
+        }
+        let recording = recording_app.world.remove_resource::<InputRecorder>().unwrap().recording;
+        let recorded = recording_app.world.remove_resource::<Observed>().unwrap().0;
+        assert_eq!(recording.frames.len(), recorded.len());
+
+        let mut replaying_app = app();
+        replaying_app.insert_resource(InputReplay::new(recording));
+        for _ in 0..recorded.len() {
+            replaying_app.update();
+        }
+        let replayed = &replaying_app.world.resource::<Observed>().0;
+        assert_eq!(&recorded, replayed);
+        assert_eq!(replayed[0].0, 0.01);
+        assert_eq!(replayed[1].1, vec![ButtonState::Pressed, ButtonState::Released]);
+        assert_eq!(replayed[2].2, Vec2::new(0.02, -0.02));
+
+        // finished after the last frame
+        replaying_app.update();
+        assert!(!replaying_app.world.contains_resource::<InputReplay>());
+    }
+}
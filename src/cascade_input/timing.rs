@@ -0,0 +1,374 @@
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, input::ButtonState};
+
+use super::{button::ButtonInput, order::{CascadeNode, CascadeOrder}};
+
+
+/// Pressed while the source is held longer than `min_duration`.
+#[derive(Component)]
+pub struct Hold<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    pub source: Entity,
+    pub min_duration: f32,
+    held: Option<f32>,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> Hold<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn new(source: Entity, min_duration: f32) -> Self {
+        Self {
+            source: source,
+            min_duration: min_duration,
+            held: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<S> CascadeNode for Hold<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+pub fn update_hold_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut Hold<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<Hold<SystemLabel>>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, mut hold) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = source.get(hold.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        for event in source.events() {
+            hold.held = match event {
+                ButtonState::Pressed => Some(0.0),
+                ButtonState::Released => None,
+            };
+        }
+        if let Some(held) = hold.held.as_mut() {
+            *held += time.delta_seconds();
+        }
+        let state = match hold.held {
+            Some(held) if hold.min_duration <= held => ButtonState::Pressed,
+            _ => ButtonState::Released,
+        };
+        // avoid false change detection
+        if !button.is(state) {
+            button.set(state);
+        }
+    }
+}
+
+
+/// Just pressed and released when the source is released within `max_duration`.
+#[derive(Component)]
+pub struct Tap<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    pub source: Entity,
+    pub max_duration: f32,
+    held: Option<f32>,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> Tap<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn new(source: Entity, max_duration: f32) -> Self {
+        Self {
+            source: source,
+            max_duration: max_duration,
+            held: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<S> CascadeNode for Tap<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+pub fn update_tap_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut Tap<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<Tap<SystemLabel>>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, mut tap) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = source.get(tap.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        if let Some(held) = tap.held.as_mut() {
+            *held += time.delta_seconds();
+        }
+        let mut tapped = false;
+        for event in source.events() {
+            match event {
+                ButtonState::Pressed => {
+                    tap.held = Some(0.0);
+                },
+                ButtonState::Released => {
+                    tapped |= tap.held.is_some_and(|held| held <= tap.max_duration);
+                    tap.held = None;
+                },
+            }
+        }
+        if tapped {
+            button.press();
+            button.release();
+        }
+    }
+}
+
+
+/// Pressed while the source is pressed again within `window` after the previous press.
+#[derive(Component)]
+pub struct DoubleTap<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    pub source: Entity,
+    pub window: f32,
+    since_first_press: Option<f32>,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> DoubleTap<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn new(source: Entity, window: f32) -> Self {
+        Self {
+            source: source,
+            window: window,
+            since_first_press: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<S> CascadeNode for DoubleTap<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+pub fn update_double_tap_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut DoubleTap<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<DoubleTap<SystemLabel>>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, mut double_tap) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = source.get(double_tap.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        if let Some(since) = double_tap.since_first_press.as_mut() {
+            *since += time.delta_seconds();
+        }
+        for event in source.events() {
+            match event {
+                ButtonState::Pressed => {
+                    if double_tap.since_first_press.is_some_and(|since| since <= double_tap.window) {
+                        double_tap.since_first_press = None;
+                        button.press();
+                    } else {
+                        double_tap.since_first_press = Some(0.0);
+                    }
+                },
+                ButtonState::Released => {
+                    // avoid false change detection
+                    if button.pressed() {
+                        button.release();
+                    }
+                },
+            }
+        }
+    }
+}
+
+
+/// Just pressed and released once when the source is held for `duration`.
+#[derive(Component)]
+pub struct LongPress<SystemLabel> where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    pub source: Entity,
+    pub duration: f32,
+    held: Option<f32>,
+    _phantom: PhantomData<SystemLabel>,
+}
+impl<S> LongPress<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    pub fn new(source: Entity, duration: f32) -> Self {
+        Self {
+            source: source,
+            duration: duration,
+            held: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<S> CascadeNode for LongPress<S>
+    where S: Clone + Eq + Send + Sync + 'static
+{
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+pub fn update_long_press_buttons<SystemLabel> (
+    mut buttons: Query<(Entity, &mut ButtonInput, &mut LongPress<SystemLabel>)>,
+    source: Query<&ButtonInput, Without<LongPress<SystemLabel>>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) where
+    SystemLabel: Clone + Eq + Send + Sync + 'static
+{
+    for (entity, mut button, mut long_press) in buttons.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = source.get(long_press.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        for event in source.events() {
+            long_press.held = match event {
+                ButtonState::Pressed => Some(0.0),
+                ButtonState::Released => None,
+            };
+        }
+        let Some(held) = long_press.held else {continue;};
+        let held = held + time.delta_seconds();
+        long_press.held = Some(held);
+        if long_press.duration <= held {
+            // fire only once per press
+            long_press.held = None;
+            button.press();
+            button.release();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{input::InputPlugin, time::TimeUpdateStrategy, window::WindowPlugin};
+
+    use super::*;
+    use crate::cascade_input::{CascadeInputPlugin, order::{CascadeAppExt, CascadeNodeSet}};
+
+    #[derive(Clone, PartialEq, Eq)]
+    struct TestLabel;
+
+    const SECONDS: f32 = 1.5;
+
+    /// Events of the node in the frame, before they are cleared.
+    #[derive(Resource, Default)]
+    struct Observed(Vec<ButtonState>);
+
+    fn observe<N: Component>(
+        mut observed: ResMut<Observed>,
+        outputs: Query<&ButtonInput, With<N>>,
+    ) {
+        observed.0 = outputs.single().events();
+    }
+
+    /// Presses the source over the intervals in seconds, and returns when the node is just pressed and released.
+    fn output_events<N: Component>(fps: u32, node: impl FnOnce(Entity) -> N, presses: &[(f32, f32)]) -> (Vec<f32>, Vec<f32>) {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+            .add_cascade_node::<Hold<TestLabel>, _>(CascadeNodeSet::Evaluate, update_hold_buttons::<TestLabel>)
+            .add_cascade_node::<Tap<TestLabel>, _>(CascadeNodeSet::Evaluate, update_tap_buttons::<TestLabel>)
+            .add_cascade_node::<DoubleTap<TestLabel>, _>(CascadeNodeSet::Evaluate, update_double_tap_buttons::<TestLabel>)
+            .add_cascade_node::<LongPress<TestLabel>, _>(CascadeNodeSet::Evaluate, update_long_press_buttons::<TestLabel>)
+            .init_resource::<Observed>()
+            .add_systems(Update, observe::<N>)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / fps as f64)));
+        let source = app.world.spawn(ButtonInput::default()).id();
+        app.world.spawn((ButtonInput::default(), node(source)));
+        // the first frame has no duration
+        app.update();
+
+        let (mut pressed, mut released) = (Vec::new(), Vec::new());
+        for frame in 1..=(SECONDS * fps as f32) as u32 {
+            let time = frame as f32 / fps as f32;
+            let state = match presses.iter().any(|&(press, release)| press < time + 1e-4 && time + 1e-4 < release) {
+                true => ButtonState::Pressed,
+                false => ButtonState::Released,
+            };
+            app.world.get_mut::<ButtonInput>(source).unwrap().set(state);
+            app.update();
+            for &event in app.world.resource::<Observed>().0.iter() {
+                match event {
+                    ButtonState::Pressed => pressed.push(time),
+                    ButtonState::Released => released.push(time),
+                }
+            }
+        }
+        (pressed, released)
+    }
+
+    /// Checks the times at 30 and 120 FPS against the expected ones, within a frame at 30 FPS.
+    fn assert_times<N: Component>(node: impl Fn(Entity) -> N, presses: &[(f32, f32)], expected_pressed: &[f32], expected_released: &[f32]) {
+        for fps in [30, 120] {
+            let (pressed, released) = output_events(fps, &node, presses);
+            for (times, expected) in [(pressed, expected_pressed), (released, expected_released)] {
+                assert_eq!(times.len(), expected.len(), "{:?} at {} FPS, expected {:?}", times, fps, expected);
+                for (time, expected) in times.iter().zip(expected.iter()) {
+                    assert!((time - expected).abs() <= 1.0 / 30.0 + 1e-4, "{:?} at {} FPS, expected {:?}", times, fps, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hold_presses_after_the_duration() {
+        assert_times(
+            |source| Hold::<TestLabel>::new(source, 0.3),
+            &[(0.1, 0.3), (0.5, 1.0)],
+            &[0.8],
+            &[1.0],
+        );
+    }
+
+    #[test]
+    fn tap_fires_on_short_releases_only() {
+        assert_times(
+            |source| Tap::<TestLabel>::new(source, 0.2),
+            &[(0.1, 0.2), (0.5, 1.0)],
+            &[0.2],
+            &[0.2],
+        );
+    }
+
+    #[test]
+    fn double_tap_presses_on_the_second_press_within_the_window() {
+        assert_times(
+            |source| DoubleTap::<TestLabel>::new(source, 0.3),
+            // the second pair is too far apart
+            &[(0.1, 0.2), (0.3, 0.5), (0.8, 0.9), (1.2, 1.3)],
+            &[0.3],
+            &[0.5],
+        );
+    }
+
+    #[test]
+    fn long_press_fires_once_per_press() {
+        assert_times(
+            |source| LongPress::<TestLabel>::new(source, 0.4),
+            &[(0.1, 0.3), (0.5, 1.4)],
+            &[0.9],
+            &[0.9],
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Moves characters either as dynamic rigid bodies or with the kinematic character controller of rapier.
+//!
+//! Both backends are driven by [`Velocity`], so that locomotion writes the same component on either.
+//! The dynamic backend leaves it to the physics.
+//! The kinematic backend adds gravity and ground friction to the velocity, moves by it with [`KinematicCharacterController`],
+//! and writes back the effective velocity, losing what walls and the ground stopped.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::locomotion_system::physics_delta_seconds;
+
+
+pub struct CharacterBackendPlugin;
+impl Plugin for CharacterBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, setup_character_backends)
+            .add_systems(PostUpdate, drive_kinematic_characters.before(PhysicsSet::SyncBackend))
+            .add_systems(PostUpdate, update_kinematic_velocities.after(PhysicsSet::Writeback))
+        ;
+    }
+}
+
+
+/// How the character body moves, inserting the rigid body and the controller on change.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharacterBackend {
+    /// Pushed by the physics, keeps momentum and is slowed by friction.
+    #[default]
+    Dynamic,
+    /// Moved by [`KinematicCharacterController`], stepping up stairs, snapping to ground and sliding along walls.
+    /// A controller already on the entity is kept, instead of [`CharacterBackend::kinematic_controller`].
+    Kinematic,
+}
+impl CharacterBackend {
+    pub fn kinematic_controller() -> KinematicCharacterController {
+        KinematicCharacterController {
+            autostep: Some(CharacterAutostep {
+                max_height: CharacterLength::Absolute(0.4),
+                min_width: CharacterLength::Absolute(0.2),
+                include_dynamic_bodies: false,
+            }),
+            snap_to_ground: Some(CharacterLength::Absolute(0.3)),
+            slide: true,
+            max_slope_climb_angle: 50f32.to_radians(),
+            min_slope_slide_angle: 50f32.to_radians(),
+            ..default()
+        }
+    }
+}
+
+fn setup_character_backends(
+    mut commands: Commands,
+    characters: Query<(Entity, &CharacterBackend, Option<&KinematicCharacterController>), Changed<CharacterBackend>>,
+) {
+    for (entity, backend, controller) in characters.iter() {
+        let mut character = commands.entity(entity);
+        match backend {
+            CharacterBackend::Dynamic => {
+                character
+                    .insert(RigidBody::Dynamic)
+                    .remove::<(KinematicCharacterController, KinematicCharacterControllerOutput)>();
+            },
+            CharacterBackend::Kinematic => {
+                character.insert(RigidBody::KinematicPositionBased);
+                if controller.is_none() {
+                    character.insert(CharacterBackend::kinematic_controller());
+                }
+            },
+        }
+    }
+}
+
+fn drive_kinematic_characters(
+    mut characters: Query<(&CharacterBackend, &mut Velocity, &mut KinematicCharacterController, Option<&KinematicCharacterControllerOutput>, Option<&Friction>)>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let delta = physics_delta_seconds(&rapier_config, &time);
+    for (backend, mut velocity, mut controller, output, friction) in characters.iter_mut() {
+        if *backend != CharacterBackend::Kinematic {continue;}
+        let mut linvel = velocity.linvel + delta * rapier_config.gravity;
+        // friction of the ground, which the kinematic body does not get from the physics
+        if let (Some(friction), true) = (friction, output.map_or(false, |output| output.grounded)) {
+            let horizontal = Vec3::new(linvel.x, 0.0, linvel.z);
+            let deceleration = (friction.coefficient * rapier_config.gravity.length() * delta).min(horizontal.length());
+            linvel -= deceleration * horizontal.normalize_or_zero();
+        }
+        // avoid false change detection
+        if velocity.linvel != linvel {
+            velocity.linvel = linvel;
+        }
+        controller.translation = Some(delta * linvel);
+    }
+}
+
+fn update_kinematic_velocities(
+    mut characters: Query<(&CharacterBackend, &mut Velocity, &KinematicCharacterControllerOutput)>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let delta = physics_delta_seconds(&rapier_config, &time);
+    if delta <= 0.0 {
+        return;
+    }
+    for (backend, mut velocity, output) in characters.iter_mut() {
+        if *backend != CharacterBackend::Kinematic {continue;}
+        // the desired velocity, the writeback of rapier overwrites it with the velocity of the body
+        let desired = output.desired_translation / delta;
+        let mut linvel = output.effective_translation / delta;
+        // stepping up and snapping down move the body without launching it
+        if output.grounded {
+            linvel.y = linvel.y.clamp(desired.y.min(0.0), desired.y.max(0.0));
+        }
+        // avoid false change detection
+        if velocity.linvel != linvel {
+            velocity.linvel = linvel;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Shrinks the capsule of the character while crouching, and lowers its friction while sliding.
+//!
+//! The character keeps its standing shape in [`CharacterCapsule`] and its friction in [`CharacterFriction`],
+//! and the states override them with [`Crouch`] and [`Slide`] on the state machine, like locomotion parameters.
+//! The origin of the character stays, so that children such as the ground sensor stay in place.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::Head;
+
+
+pub struct CrouchPlugin;
+impl Plugin for CrouchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (update_character_capsules, move_heads_to_stance).chain())
+            .add_systems(Update, update_character_friction)
+        ;
+    }
+}
+
+
+/// Standing shape of the character, a capsule along y around the origin.
+/// The collider of the character is rewritten from this.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct CharacterCapsule {
+    pub half_height: f32,
+    pub radius: f32,
+    /// Speed of the head following the stance, in meters per second.
+    pub head_speed: f32,
+    applied: Option<Crouch>,
+}
+impl CharacterCapsule {
+    pub fn new(half_height: f32, radius: f32) -> Self {
+        Self {
+            half_height: half_height,
+            radius: radius,
+            head_speed: 6.0,
+            applied: None,
+        }
+    }
+    pub fn collider(&self) -> Collider {
+        Collider::capsule_y(self.half_height, self.radius)
+    }
+    /// Crouch of the current collider.
+    pub fn crouch(&self) -> Option<Crouch> {
+        self.applied
+    }
+    /// Offset of the collider and the head from standing.
+    fn offsets(&self, crouch: Option<Crouch>) -> (f32, f32) {
+        let Some(crouch) = crouch else {
+            return (0.0, 0.0);
+        };
+        let shrink = (self.half_height - crouch.half_height).max(0.0);
+        match crouch.anchor {
+            CrouchAnchor::Feet => (-shrink, -2.0 * shrink),
+            CrouchAnchor::Head => (shrink, 0.0),
+        }
+    }
+    /// Crouched capsule and its center relative to the origin.
+    pub fn crouched_shape(&self) -> (Collider, f32) {
+        let half_height = self.applied.map_or(self.half_height, |crouch| crouch.half_height.min(self.half_height));
+        (Collider::capsule_y(half_height, self.radius), self.offsets(self.applied).0)
+    }
+    /// How far the capsule grows on standing up, and the direction.
+    pub fn stand_up_motion(&self) -> Option<(f32, Vec3)> {
+        let crouch = self.applied?;
+        let growth = 2.0 * (self.half_height - crouch.half_height).max(0.0);
+        match crouch.anchor {
+            CrouchAnchor::Feet => Some((growth, Vec3::Y)),
+            CrouchAnchor::Head => Some((growth, Vec3::NEG_Y)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrouchAnchor {
+    /// Keeps the feet, lowering the head, on ground.
+    Feet,
+    /// Keeps the head, tucking the feet, in air.
+    Head,
+}
+
+/// Parent shrinks the capsule into `half_height`.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct Crouch {
+    pub half_height: f32,
+    pub anchor: CrouchAnchor,
+}
+impl Crouch {
+    pub fn feet(half_height: f32) -> Self {
+        Self { half_height, anchor: CrouchAnchor::Feet }
+    }
+    pub fn head(half_height: f32) -> Self {
+        Self { half_height, anchor: CrouchAnchor::Head }
+    }
+}
+
+fn update_character_capsules(
+    mut characters: Query<(Entity, &mut CharacterCapsule, &mut Collider)>,
+    params: Query<(&Crouch, &Parent)>,
+) {
+    let crouches: HashMap<Entity, Crouch> = params.iter().map(|(crouch, parent)| (parent.get(), *crouch)).collect();
+    for (character, mut capsule, mut collider) in characters.iter_mut() {
+        let crouch = crouches.get(&character).copied();
+        if capsule.applied == crouch {
+            continue;
+        }
+        capsule.applied = crouch;
+        let (shape, offset) = capsule.crouched_shape();
+        *collider = if offset == 0.0 {
+            shape
+        } else {
+            Collider::compound(vec![(offset * Vec3::Y, Quat::IDENTITY, shape)])
+        };
+    }
+}
+
+fn move_heads_to_stance(
+    characters: Query<&CharacterCapsule>,
+    mut heads: Query<(&mut Transform, &Parent), With<Head>>,
+    time: Res<Time>,
+) {
+    for (mut transform, parent) in heads.iter_mut() {
+        let Ok(capsule) = characters.get(parent.get()) else {continue;};
+        let target = capsule.offsets(capsule.applied).1;
+        let step = capsule.head_speed * time.delta_seconds();
+        let y = transform.translation.y + (target - transform.translation.y).clamp(-step, step);
+        // avoid false change detection
+        if transform.translation.y != y {
+            transform.translation.y = y;
+        }
+    }
+}
+
+
+/// Friction of the character while no state overrides.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct CharacterFriction {
+    pub friction: Friction,
+}
+
+/// Parent slides with the friction, keeping momentum.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct Slide {
+    pub friction: Friction,
+}
+impl Default for Slide {
+    fn default() -> Self {
+        // the minimum, or the ground would keep the average high
+        Self { friction: Friction { coefficient: 0.05, combine_rule: CoefficientCombineRule::Min } }
+    }
+}
+
+fn update_character_friction(
+    mut characters: Query<(Entity, &CharacterFriction, &mut Friction)>,
+    params: Query<(&Slide, &Parent)>,
+) {
+    let slides: HashMap<Entity, Friction> = params.iter().map(|(slide, parent)| (parent.get(), slide.friction)).collect();
+    for (character, base, mut friction) in characters.iter_mut() {
+        let target = slides.get(&character).copied().unwrap_or(base.friction);
+        // avoid false change detection
+        if *friction != target {
+            *friction = target;
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Finds the ground under the foot sensor of a character, with its normal, to walk along slopes.
+//!
+//! [`GroundProbe`] casts the sensor collider downward from slightly above itself every frame,
+//! and keeps the hit in [`GroundInfo`] on the same entity, updated in place so that it is always present.
+//! The ground supports the character only while the sensor reaches it and it is not steeper than the limit.
+//! Ground the cast starts inside has no defined normal, so it is not recorded and supports nothing.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::global_settings::NamedCollisionGroup;
+
+
+pub struct GroundProbePlugin;
+impl Plugin for GroundProbePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, probe_ground.in_set(GroundProbeSet))
+        ;
+    }
+}
+
+/// Systems reading [`GroundInfo`] in Update run after this.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub struct GroundProbeSet;
+
+
+/// Probes the ground under the sensor collider of the entity, excluding the parent body.
+/// Records into [`GroundInfo`] on the same entity.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct GroundProbe {
+    /// Steepest ground to stand on, in radians from horizontal.
+    pub max_slope_angle: f32,
+    /// How far below the sensor the ground is still recorded.
+    pub max_distance: f32,
+}
+impl Default for GroundProbe {
+    fn default() -> Self {
+        Self { max_slope_angle: 50f32.to_radians(), max_distance: 0.5 }
+    }
+}
+impl GroundProbe {
+    /// Height above the sensor where the cast starts, so that ground the sensor overlaps is hit with a normal.
+    const LIFT: f32 = 0.25;
+
+    /// Whether the ground holds the character up.
+    pub fn supports(&self, hit: &GroundHit) -> bool {
+        hit.distance <= 0.0 && hit.slope_angle() <= self.max_slope_angle
+    }
+}
+
+/// Ground found by [`GroundProbe`], none while nothing is under.
+#[derive(Debug, Component, Default, Clone, Copy, PartialEq)]
+pub struct GroundInfo {
+    pub hit: Option<GroundHit>,
+}
+
+/// Ground under the sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroundHit {
+    pub entity: Entity,
+    /// Normal of the ground in global space, pointing from the ground.
+    pub normal: Vec3,
+    /// Gap between the sensor and the ground, negative while overlapping.
+    pub distance: f32,
+    /// Contact point in global space.
+    pub point: Vec3,
+}
+impl GroundHit {
+    /// Angle of the ground from horizontal, in radians.
+    pub fn slope_angle(&self) -> f32 {
+        self.normal.angle_between(Vec3::Y)
+    }
+    /// Projects onto the plane of the ground, keeping the length.
+    pub fn project(&self, vector: Vec3) -> Vec3 {
+        let projected = vector - vector.dot(self.normal) * self.normal;
+        if 0.0 < projected.length() {
+            vector.length() * projected.normalize()
+        } else {
+            vector
+        }
+    }
+}
+
+fn probe_ground(
+    mut probes: Query<(&GroundProbe, &Collider, &GlobalTransform, &Parent, &mut GroundInfo)>,
+    rapier_context: Res<RapierContext>,
+) {
+    for (probe, collider, transform, parent, mut ground) in probes.iter_mut() {
+        let (_scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let origin = translation + GroundProbe::LIFT * Vec3::Y;
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(parent.get())
+            .exclude_sensors()
+            .groups(CollisionGroups::new(NamedCollisionGroup::ALL, NamedCollisionGroup::TERRAIN | NamedCollisionGroup::OBJECT));
+        let hit = rapier_context.cast_shape(origin, rotation, Vec3::NEG_Y, collider, GroundProbe::LIFT + probe.max_distance, filter);
+        // normals are undefined when the cast starts inside
+        let hit = hit
+            .filter(|(_ground, toi)| !matches!(toi.status, TOIStatus::Penetrating))
+            .map(|(ground, toi)| GroundHit {
+                entity: ground,
+                normal: -rotation.mul_vec3(toi.normal2),
+                distance: toi.toi - GroundProbe::LIFT,
+                point: origin + toi.toi * Vec3::NEG_Y + rotation.mul_vec3(toi.witness2),
+            });
+        // avoid false change detection
+        if ground.hit != hit {
+            ground.hit = hit;
+        }
+    }
+}
@@ -3,11 +3,16 @@ use bevy_rapier3d::prelude::*;
 use seldom_state::prelude::*;
 
 use crate::{
-    util::state_machine::{insert_while_state, Timeout},
-    cascade_input::button::{ButtonTrigger, ButtonJustPressedTrigger}, global_settings::NamedCollisionGroup,
+    util::state_machine::insert_while_state,
+    cascade_input::button::{ButtonTrigger, ButtonJustPressedTrigger, ButtonJustReleasedTrigger}, global_settings::NamedCollisionGroup,
 };
 
-use super::locomotion_system::{BasicLocomotion, AirborneLocomotion, JumpUp, CharacterRotation, HeadRotation};
+use super::{
+    locomotion_system::{BasicLocomotion, AirborneLocomotion, JumpUp, CharacterRotation, HeadRotation, PhysicsTimeout},
+    crouch::{CharacterCapsule, Crouch, Slide},
+    ground::{GroundProbe, GroundInfo},
+    stamina::{DrainStamina, StaminaCost, ExhaustedTrigger},
+};
 
 
 #[derive(Bundle)]
@@ -18,6 +23,8 @@ pub struct GroundedStateMachineBundle {
     pub label: GroundedStateMachine,
     pub sensor_label: Sensor,
     pub collision_groups: CollisionGroups,
+    pub ground_probe: GroundProbe,
+    pub ground: GroundInfo,
     pub initial_state: Grounded,
 }
 impl Default for GroundedStateMachineBundle {
@@ -29,6 +36,8 @@ impl Default for GroundedStateMachineBundle {
             label: GroundedStateMachine,
             sensor_label: Sensor,
             collision_groups: CollisionGroups::new(NamedCollisionGroup::PURE_SENSOR, NamedCollisionGroup::TERRAIN | NamedCollisionGroup::OBJECT),
+            ground_probe: GroundProbe::default(),
+            ground: GroundInfo::default(),
             initial_state: Grounded,
         }
     }
@@ -45,32 +54,217 @@ pub struct Airborne;
 #[derive(Clone, Component, Reflect)]
 #[component(storage = "SparseSet")]
 pub struct JumpingUp;
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct Crouching;
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct Sliding;
+/// Running fast, draining stamina.
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct Sprinting;
+/// Out of stamina, unable to sprint until recovered.
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct Exhausted;
+/// Left the ground without jumping, still allowed to jump for a while.
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct Coyote;
+/// Jump pressed in air, jumping on landing for a while.
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct JumpBuffered;
+/// Crouching in air, tucking the legs to reach higher.
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+pub struct AirborneCrouching;
 
+/// Sensor on ground not steeper than the limit of its [`GroundProbe`].
 #[derive(Copy, Clone)]
 pub struct GroundContact;
 impl BoolTrigger for GroundContact {
-    type Param<'w, 's> = Res<'w, RapierContext>;
+    type Param<'w, 's> = Query<'w, 's, (&'static GroundProbe, &'static GroundInfo)>;
+    fn trigger(
+        &self,
+        entity: Entity,
+        probes: Self::Param<'_, '_>,
+    ) -> bool {
+        let Ok((probe, ground)) = probes.get(entity) else {
+            warn!("Entity not found!");
+            return false;
+        };
+        ground.hit.map_or(false, |hit| probe.supports(&hit))
+    }
+}
+
+/// Room for the parent to stand up, found by casting the crouched capsule toward growing.
+#[derive(Copy, Clone)]
+pub struct StandingRoom;
+impl StandingRoom {
+    /// Gap kept from the surfaces touching the crouched capsule.
+    const SKIN: f32 = 0.05;
+}
+impl BoolTrigger for StandingRoom {
+    type Param<'w, 's> = (
+        Res<'w, RapierContext>,
+        Query<'w, 's, &'static Parent>,
+        Query<'w, 's, (&'static CharacterCapsule, &'static GlobalTransform)>,
+    );
     fn trigger(
         &self,
         entity: Entity,
-        rapier_context: Self::Param<'_, '_>,
+        (rapier_context, parents, characters): Self::Param<'_, '_>,
     ) -> bool {
-        let intersections = rapier_context.intersections_with(entity);
-        0 < intersections.count()
+        let Ok(parent) = parents.get(entity) else {
+            warn!("Parent not found!");
+            return false;
+        };
+        let Ok((capsule, transform)) = characters.get(parent.get()) else {
+            warn!("Entity not found!");
+            return false;
+        };
+        let Some((distance, direction)) = capsule.stand_up_motion() else {
+            return true;
+        };
+        let (_scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let (shape, offset) = capsule.crouched_shape();
+        let Some(capsule_shape) = shape.as_capsule() else {
+            return true;
+        };
+        let shape = Collider::capsule(capsule_shape.segment().a(), capsule_shape.segment().b(), capsule_shape.radius() - Self::SKIN);
+        let direction = rotation.mul_vec3(direction);
+        let position = translation + rotation.mul_vec3(offset * Vec3::Y) + Self::SKIN * direction;
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(parent.get())
+            .exclude_sensors()
+            .groups(CollisionGroups::new(NamedCollisionGroup::ALL, NamedCollisionGroup::TERRAIN | NamedCollisionGroup::OBJECT));
+        rapier_context.cast_shape(position, rotation, direction, &shape, distance - Self::SKIN, filter).is_none()
+    }
+}
+
+/// Parent moving horizontally at `min_speed` or faster.
+#[derive(Copy, Clone)]
+pub struct HorizontalSpeedTrigger {
+    pub min_speed: f32,
+}
+impl BoolTrigger for HorizontalSpeedTrigger {
+    type Param<'w, 's> = (Query<'w, 's, &'static Parent>, Query<'w, 's, &'static Velocity>);
+    fn trigger(
+        &self,
+        entity: Entity,
+        (parents, velocities): Self::Param<'_, '_>,
+    ) -> bool {
+        let Some(velocity) = parents.get(entity).ok().and_then(|parent| velocities.get(parent.get()).ok()) else {
+            warn!("Parent does not have velocity!");
+            return false;
+        };
+        self.min_speed <= Vec2::new(velocity.linvel.x, velocity.linvel.z).length()
+    }
+}
+
+/// Forgiveness of the jump timing, in seconds, zero to disable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JumpAssist {
+    /// Jumping is still allowed after walking off a ledge.
+    pub coyote_time: f32,
+    /// Jump pressed before landing executes on the touchdown.
+    pub buffer_time: f32,
+}
+impl Default for JumpAssist {
+    fn default() -> Self {
+        Self { coyote_time: 0.1, buffer_time: 0.15 }
     }
 }
 
 impl GroundedStateMachine {
+    /// Speed of [`SprintingDefaultBundle`].
+    pub const SPRINT_SPEED: f32 = 7.0;
+    /// Speed to start sliding on crouch, instead of crouching.
+    /// Above the running speed of [`BasicLocomotion`], so that only sprints slide.
+    pub const SLIDE_MIN_SPEED: f32 = Self::SPRINT_SPEED - 1.5;
+    /// Speed to stop sliding into crouching.
+    pub const SLIDE_END_SPEED: f32 = 1.5;
+    /// Speed to keep sprinting, below which the sprint is released.
+    pub const SPRINT_MIN_SPEED: f32 = 1.0;
+
+    /// Jump presses are buffered by the machine itself, as set by `assist`,
+    /// so `jump_button` needs no `BufferedPress`.
+    /// Releasing `jump_button` while jumping up ends the jump early, so a buffered or coyote jump
+    /// already released before the takeoff jumps fully.
+    /// Stays crouching while something overhead blocks standing up.
+    /// Sprints while `sprint_button` is held and moving, unless exhausted.
     pub fn default_machine (
         jump_button: Entity,
+        crouch_button: Entity,
+        sprint_button: Entity,
+        assist: JumpAssist,
     ) -> StateMachine {
         let ground_contact = GroundContact;
-        StateMachine::default()
-            .trans::<Grounded>(ground_contact.not(), Airborne)
+        let jump = ButtonJustPressedTrigger { button: jump_button };
+        let crouch = ButtonTrigger { button: crouch_button };
+        let sprint = ButtonTrigger { button: sprint_button };
+        let crouch_at_speed = || (ButtonJustPressedTrigger { button: crouch_button }).and(HorizontalSpeedTrigger { min_speed: Self::SLIDE_MIN_SPEED });
+        let machine = StateMachine::default();
+        let machine = if 0.0 < assist.coyote_time {
+            let machine = machine
+                .trans::<Grounded>(ground_contact.not(), Coyote)
+                .trans::<Sprinting>(ground_contact.not(), Coyote)
+                .trans::<Exhausted>(ground_contact.not(), Coyote)
+                .trans::<Coyote>(ground_contact, Grounded)
+                .trans::<Coyote>(jump, JumpingUp)
+                .trans::<Coyote>(DoneTrigger::Success, Airborne)
+            ;
+            insert_while_state::<Coyote, _>(machine, PhysicsTimeout::new(assist.coyote_time))
+        } else {
+            machine
+                .trans::<Grounded>(ground_contact.not(), Airborne)
+                .trans::<Sprinting>(ground_contact.not(), Airborne)
+                .trans::<Exhausted>(ground_contact.not(), Airborne)
+        };
+        let machine = if 0.0 < assist.buffer_time {
+            let machine = machine
+                .trans::<JumpBuffered>(ground_contact, JumpingUp)
+                .trans::<JumpBuffered>(DoneTrigger::Success, Airborne)
+                .trans::<Airborne>(jump.and(ground_contact.not()), JumpBuffered)
+            ;
+            insert_while_state::<JumpBuffered, _>(machine, PhysicsTimeout::new(assist.buffer_time))
+        } else {
+            machine
+        };
+        machine
             .trans::<Airborne>(ground_contact, Grounded)
-            .trans::<Grounded>(ButtonJustPressedTrigger { button: jump_button }, JumpingUp)
-            .trans::<JumpingUp>((ButtonTrigger { button: jump_button }).not(), Airborne)
+            .trans::<Grounded>(jump, JumpingUp)
+            .trans::<JumpingUp>(ButtonJustReleasedTrigger { button: jump_button }, Airborne)
             .trans::<JumpingUp>(DoneTrigger::Success, Airborne)
+            // crouching, or sliding if pressed at speed
+            .trans::<Grounded>(crouch_at_speed(), Sliding)
+            .trans::<Grounded>(crouch, Crouching)
+            .trans::<Crouching>(ground_contact.not(), AirborneCrouching)
+            .trans::<Crouching>(jump.and(StandingRoom), JumpingUp)
+            .trans::<Crouching>(StandingRoom.and(crouch.not()), Grounded)
+            .trans::<Sliding>(ground_contact.not(), AirborneCrouching)
+            .trans::<Sliding>(jump.and(StandingRoom), JumpingUp)
+            .trans::<Sliding>(StandingRoom.and(crouch.not()), Grounded)
+            .trans::<Sliding>((HorizontalSpeedTrigger { min_speed: Self::SLIDE_END_SPEED }).not(), Crouching)
+            .trans::<Sliding>(DoneTrigger::Success, Crouching)
+            // sprinting, blocked while exhausted
+            .trans::<Grounded>(ExhaustedTrigger, Exhausted)
+            .trans::<Grounded>(sprint.and(HorizontalSpeedTrigger { min_speed: Self::SPRINT_MIN_SPEED }), Sprinting)
+            .trans::<Sprinting>(jump, JumpingUp)
+            .trans::<Sprinting>(crouch_at_speed(), Sliding)
+            .trans::<Sprinting>(ExhaustedTrigger, Exhausted)
+            .trans::<Sprinting>(sprint.not(), Grounded)
+            .trans::<Sprinting>((HorizontalSpeedTrigger { min_speed: Self::SPRINT_MIN_SPEED }).not(), Grounded)
+            .trans::<Exhausted>(jump, JumpingUp)
+            .trans::<Exhausted>(crouch_at_speed(), Sliding)
+            .trans::<Exhausted>(crouch, Crouching)
+            .trans::<Exhausted>(ExhaustedTrigger.not(), Grounded)
+            // crouch-jumping
+            .trans::<Airborne>(crouch, AirborneCrouching)
+            .trans::<AirborneCrouching>(ground_contact, Crouching)
+            .trans::<AirborneCrouching>(StandingRoom.and(crouch.not()), Airborne)
             .set_trans_logging(true)
     }
     pub fn set_state_components_sample (
@@ -78,7 +272,14 @@ impl GroundedStateMachine {
     ) -> StateMachine {
         let state_machine = insert_while_state::<Grounded, _>(state_machine, GroundedDefaultBundle::default());
         let state_machine = insert_while_state::<Airborne, _>(state_machine, AirborneDefaultBundle::default());
+        let state_machine = insert_while_state::<Sprinting, _>(state_machine, SprintingDefaultBundle::default());
+        let state_machine = insert_while_state::<Exhausted, _>(state_machine, ExhaustedDefaultBundle::default());
+        let state_machine = insert_while_state::<Coyote, _>(state_machine, AirborneDefaultBundle::default());
+        let state_machine = insert_while_state::<JumpBuffered, _>(state_machine, AirborneDefaultBundle::default());
         let state_machine = insert_while_state::<JumpingUp, _>(state_machine, JumpingUpDefaultBundle::default());
+        let state_machine = insert_while_state::<Crouching, _>(state_machine, CrouchingDefaultBundle::default());
+        let state_machine = insert_while_state::<Sliding, _>(state_machine, SlidingDefaultBundle::default());
+        let state_machine = insert_while_state::<AirborneCrouching, _>(state_machine, AirborneCrouchingDefaultBundle::default());
         state_machine
     }
 }
@@ -88,6 +289,30 @@ pub struct GroundedDefaultBundle {
     pub rotation: CharacterRotation,
     pub head_rotation: HeadRotation,
 }
+#[derive(Bundle, Clone, Copy)]
+pub struct SprintingDefaultBundle {
+    pub locomotion: BasicLocomotion,
+    pub drain: DrainStamina,
+    pub rotation: CharacterRotation,
+    pub head_rotation: HeadRotation,
+}
+impl Default for SprintingDefaultBundle {
+    fn default() -> Self {
+        Self {
+            locomotion: BasicLocomotion { speed: GroundedStateMachine::SPRINT_SPEED, ..default() },
+            drain: DrainStamina { rate: 20.0 },
+            rotation: CharacterRotation,
+            head_rotation: HeadRotation,
+        }
+    }
+}
+/// Runs at the normal speed, the machine only blocks sprinting.
+#[derive(Bundle, Default, Clone, Copy)]
+pub struct ExhaustedDefaultBundle {
+    pub locomotion: BasicLocomotion,
+    pub rotation: CharacterRotation,
+    pub head_rotation: HeadRotation,
+}
 #[derive(Bundle, Default, Clone, Copy)]
 pub struct AirborneDefaultBundle {
     pub locomotion: AirborneLocomotion,
@@ -96,8 +321,9 @@ pub struct AirborneDefaultBundle {
 }
 #[derive(Bundle, Clone)]
 pub struct JumpingUpDefaultBundle {
-    pub timeout: Timeout,
+    pub timeout: PhysicsTimeout,
     pub jump: JumpUp,
+    pub cost: StaminaCost,
     pub locomotion: BasicLocomotion,
     pub rotation: CharacterRotation,
     pub head_rotation: HeadRotation,
@@ -105,11 +331,205 @@ pub struct JumpingUpDefaultBundle {
 impl Default for JumpingUpDefaultBundle {
     fn default() -> Self {
         Self {
-            timeout: Timeout::new(0.1),
+            timeout: PhysicsTimeout::new(0.1),
             jump: JumpUp::default(),
+            cost: StaminaCost { amount: 10.0 },
             locomotion: BasicLocomotion::default(),
             rotation: CharacterRotation,
             head_rotation: HeadRotation,
         }
     }
 }
+#[derive(Bundle, Clone, Copy)]
+pub struct CrouchingDefaultBundle {
+    pub locomotion: BasicLocomotion,
+    pub rotation: CharacterRotation,
+    pub head_rotation: HeadRotation,
+    pub crouch: Crouch,
+}
+impl Default for CrouchingDefaultBundle {
+    fn default() -> Self {
+        Self {
+            locomotion: BasicLocomotion { speed: 2.0, ..default() },
+            rotation: CharacterRotation,
+            head_rotation: HeadRotation,
+            crouch: Crouch::feet(0.75),
+        }
+    }
+}
+#[derive(Bundle, Clone)]
+pub struct SlidingDefaultBundle {
+    pub timeout: PhysicsTimeout,
+    pub slide: Slide,
+    pub rotation: CharacterRotation,
+    pub head_rotation: HeadRotation,
+    pub crouch: Crouch,
+}
+impl Default for SlidingDefaultBundle {
+    fn default() -> Self {
+        Self {
+            timeout: PhysicsTimeout::new(1.0),
+            slide: Slide::default(),
+            rotation: CharacterRotation,
+            head_rotation: HeadRotation,
+            crouch: Crouch::feet(0.75),
+        }
+    }
+}
+#[derive(Bundle, Clone, Copy)]
+pub struct AirborneCrouchingDefaultBundle {
+    pub locomotion: AirborneLocomotion,
+    pub rotation: CharacterRotation,
+    pub head_rotation: HeadRotation,
+    pub crouch: Crouch,
+}
+impl Default for AirborneCrouchingDefaultBundle {
+    fn default() -> Self {
+        Self {
+            locomotion: AirborneLocomotion::default(),
+            rotation: CharacterRotation,
+            head_rotation: HeadRotation,
+            crouch: Crouch::head(0.75),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{input::InputPlugin, window::WindowPlugin, time::TimeUpdateStrategy, render::mesh::MeshPlugin, scene::ScenePlugin};
+
+    use super::*;
+    use crate::{
+        cascade_input::{CascadeInputPlugin, button::ButtonInput, axis::PositionalInput},
+        character_control::{CharacterControlPlugin, AttachedInput, Locomotion, stamina::Stamina},
+        util::state_machine::StateMachineUtilPlugin,
+    };
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    struct Character {
+        body: Entity,
+        machine: Entity,
+        jump: Entity,
+        crouch: Entity,
+        sprint: Entity,
+    }
+
+    /// A ledge ending at `x = 0`, with the character standing at `x` and walking toward `walk`.
+    fn app(x: f32, walk: Vec3, assist: JumpAssist) -> (App, Character) {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), TransformPlugin, HierarchyPlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins((RapierPhysicsPlugin::<NoUserData>::default(), StateMachinePlugin, StateMachineUtilPlugin, CascadeInputPlugin, CharacterControlPlugin))
+            .insert_resource(RapierConfiguration { timestep_mode: TimestepMode::Fixed { dt: FRAME, substeps: 1 }, ..default() })
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(FRAME)));
+        let world = &mut app.world;
+        world.spawn((TransformBundle::from_transform(Transform::from_xyz(-5.0, -0.5, 0.0)), Collider::cuboid(5.0, 0.5, 5.0)));
+        world.spawn((TransformBundle::from_transform(Transform::from_xyz(20.0, -20.5, 0.0)), Collider::cuboid(15.0, 0.5, 5.0)));
+        let jump = world.spawn(ButtonInput::default()).id();
+        let crouch = world.spawn(ButtonInput::default()).id();
+        let sprint = world.spawn(ButtonInput::default()).id();
+        let locomotion = world.spawn(PositionalInput { value: walk }).id();
+        let machine = GroundedStateMachine::default_machine(jump, crouch, sprint, assist);
+        let machine = world.spawn(GroundedStateMachineBundle {
+            state_machine: GroundedStateMachine::set_state_components_sample(machine),
+            sensor: Collider::ball(0.2),
+            transform: TransformBundle::from_transform(Transform::from_xyz(0.0, -1.7, 0.0)),
+            ..default()
+        }).id();
+        let capsule = CharacterCapsule::new(1.5, 0.3);
+        let body = world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(x, 1.85, 0.0)),
+            RigidBody::Dynamic,
+            Velocity::default(),
+            LockedAxes::ROTATION_LOCKED,
+            capsule.collider(),
+            capsule,
+            Stamina::new(100.0),
+            AttachedInput::<Locomotion>::new(locomotion),
+        )).add_child(machine).id();
+        (app, Character { body, machine, jump, crouch, sprint })
+    }
+    /// Runs until the machine leaves `state`, and returns the number of frames run.
+    fn run_while<S: Component>(app: &mut App, character: &Character, max_frames: usize) -> Option<usize> {
+        (1..=max_frames).find(|_| {
+            app.update();
+            !app.world.entity(character.machine).contains::<S>()
+        })
+    }
+    /// Holds the jump button for `hold` frames, and returns the highest upward speed meanwhile and shortly after.
+    fn jump(app: &mut App, character: &Character, hold: usize) -> f32 {
+        app.world.get_mut::<ButtonInput>(character.jump).unwrap().press();
+        (0..hold + 10).map(|frame| {
+            if frame == hold {
+                app.world.get_mut::<ButtonInput>(character.jump).unwrap().release();
+            }
+            app.update();
+            app.world.get::<Velocity>(character.body).unwrap().linvel.y
+        }).fold(f32::MIN, f32::max)
+    }
+    fn edge_jump(assist: JumpAssist, delay: usize) -> f32 {
+        let (mut app, character) = app(-1.0, Vec3::X, assist);
+        run_while::<Grounded>(&mut app, &character, 30).expect("not landed on the ledge");
+        run_while::<Airborne>(&mut app, &character, 30);
+        assert!(app.world.entity(character.machine).contains::<Grounded>());
+        run_while::<Grounded>(&mut app, &character, 120).expect("not walked off the ledge");
+        for _ in 0..delay {
+            app.update();
+        }
+        jump(&mut app, &character, 10)
+    }
+
+    #[test]
+    fn edge_jumps_within_coyote_time() {
+        let assist = JumpAssist::default();
+        let delay = (0.5 * assist.coyote_time / FRAME) as usize;
+        assert!(1.0 < edge_jump(assist, delay));
+        assert!(edge_jump(assist, delay + (assist.coyote_time / FRAME) as usize) < 0.0);
+        assert!(edge_jump(JumpAssist { coyote_time: 0.0, ..assist }, delay) < 0.0);
+    }
+
+    #[test]
+    fn buffered_jump_executes_on_landing() {
+        for (assist, jumps) in [(JumpAssist::default(), true), (JumpAssist { buffer_time: 0.0, ..default() }, false)] {
+            let (mut app, character) = app(-2.0, Vec3::ZERO, assist);
+            app.world.get_mut::<Transform>(character.body).unwrap().translation.y = 3.0;
+            run_while::<Grounded>(&mut app, &character, 30).expect("not falling");
+            // press a few frames before touching down
+            let fall_frames = (0..120).find(|_| {
+                let velocity = app.world.get::<Velocity>(character.body).unwrap().linvel.y;
+                let height = app.world.get::<Transform>(character.body).unwrap().translation.y - 1.85;
+                app.update();
+                height + 3.0 * FRAME * velocity < 0.0
+            }).expect("not landed");
+            assert!(0 < fall_frames);
+            // released before touching down
+            assert_eq!(1.0 < jump(&mut app, &character, 1), jumps);
+        }
+    }
+
+    #[test]
+    fn crouching_slides_only_from_a_sprint() {
+        for sprint in [false, true] {
+            let (mut app, character) = app(-1.0, Vec3::NEG_X, JumpAssist::default());
+            if sprint {
+                app.world.get_mut::<ButtonInput>(character.sprint).unwrap().press();
+            }
+            for _ in 0..60 {
+                app.update();
+            }
+            let velocity = app.world.get::<Velocity>(character.body).unwrap().linvel;
+            let speed = Vec2::new(velocity.x, velocity.z).length();
+            assert_eq!(app.world.entity(character.machine).contains::<Sprinting>(), sprint);
+            assert_eq!(GroundedStateMachine::SLIDE_MIN_SPEED < speed, sprint, "{} m/s", speed);
+            assert!(BasicLocomotion::default().speed - 0.1 < speed);
+
+            app.world.get_mut::<ButtonInput>(character.crouch).unwrap().press();
+            app.update();
+            assert_eq!(app.world.entity(character.machine).contains::<Sliding>(), sprint);
+            assert_eq!(app.world.entity(character.machine).contains::<Crouching>(), !sprint);
+        }
+    }
+}
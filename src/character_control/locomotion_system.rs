@@ -1,45 +1,94 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use seldom_state::prelude::*;
 
 use crate::cascade_input::axis::{PositionalInput, RotationalInput};
 
-use super::{Rotation, AttachedInput, HeadAttitude, Locomotion, Head};
+use super::{Rotation, AttachedInput, HeadAttitude, Locomotion, Head, ground::{GroundProbe, GroundInfo, GroundProbeSet}};
 
 
 pub struct LocomotionSystemPlugin;
 impl Plugin for LocomotionSystemPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, (jump_up, character_rotation, head_rotation))
-            .add_systems(Update, (basic_locomotion, airborne_locomotion).after(character_rotation))
+            .add_systems(Update, (jump_up.before(physics_timeout), character_rotation, head_rotation, physics_timeout))
+            .add_systems(Update, (basic_locomotion, airborne_locomotion).after(character_rotation).after(GroundProbeSet))
         ;
     }
 }
 
 
+/// Seconds the physics advances in this frame, by which velocities change.
+/// Follows the timestep mode of rapier, so that movement does not depend on the frame rate.
+pub fn physics_delta_seconds(rapier_config: &RapierConfiguration, time: &Time) -> f32 {
+    match rapier_config.timestep_mode {
+        TimestepMode::Fixed { dt, .. } => dt,
+        TimestepMode::Variable { max_dt, time_scale, .. } => (time.delta_seconds() * time_scale).min(max_dt),
+        TimestepMode::Interpolated { time_scale, .. } => time.delta_seconds() * time_scale,
+    }
+}
+
+/// Done after duration in the time the physics advanced, so that the state lasts as many physics steps at any frame rate.
+/// For locomotion states, other states time out by [`Timeout`](crate::util::state_machine::Timeout).
+#[derive(Component, Clone)]
+pub struct PhysicsTimeout {
+    pub timer: Timer,
+}
+impl PhysicsTimeout {
+    pub fn new(duration: f32) -> Self {
+        Self { timer: Timer::from_seconds(duration, TimerMode::Once) }
+    }
+}
+pub fn physics_timeout (
+    mut commands: Commands,
+    mut state_machines: Query<(Entity, &mut PhysicsTimeout)>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let delta = Duration::from_secs_f32(physics_delta_seconds(&rapier_config, &time));
+    for (state_machine, mut timeout) in state_machines.iter_mut() {
+        timeout.timer.tick(delta);
+        if timeout.timer.finished() {
+            commands.entity(state_machine).insert(Done::Success);
+        }
+    }
+}
+
+
 /// Parent continues to accelerate to target velocity.
+/// Cancels falling, and accelerates only for the time left of [`PhysicsTimeout`] if any,
+/// so that the jump does not depend on the frame rate.
 #[derive(Component, Clone, Copy)]
 pub struct JumpUp {
     pub target_velocity: Vec3,
+    /// In meters per second squared.
     pub max_acceleration: f32,
 }
 impl Default for JumpUp {
     fn default() -> Self {
-        Self { target_velocity: 20.0 * Vec3::Y, max_acceleration: 1.0 }
+        Self { target_velocity: 20.0 * Vec3::Y, max_acceleration: 60.0 }
     }
 }
 pub fn jump_up (
-    mut params: Query<(&mut JumpUp, &Parent)>,
+    mut params: Query<(&mut JumpUp, &Parent, Option<&PhysicsTimeout>)>,
     mut velocities: Query<&mut Velocity>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
 ) {
-    for (param, parent) in params.iter_mut() {
+    let delta = physics_delta_seconds(&rapier_config, &time);
+    for (param, parent, timeout) in params.iter_mut() {
+        let delta = timeout.map_or(delta, |timeout| delta.min(timeout.timer.remaining_secs()));
         let Ok(mut velocity) = velocities.get_mut(parent.get()) else {
             warn!("Parent does not have velocity!");
             continue;
         };
         let target_direction = param.target_velocity.normalize();
-        let speed_diff = param.target_velocity.length() - velocity.linvel.dot(target_direction);
-        let linvel = velocity.linvel + speed_diff.clamp(0.0, param.max_acceleration) * target_direction;
+        // cancel falling first, which depends on the frame the jump starts
+        let linvel = velocity.linvel - velocity.linvel.dot(target_direction).min(0.0) * target_direction;
+        let speed_diff = param.target_velocity.length() - linvel.dot(target_direction);
+        let linvel = linvel + speed_diff.clamp(0.0, delta * param.max_acceleration) * target_direction;
         // avoid false change detection
         if velocity.linvel != linvel {
             velocity.linvel = linvel;
@@ -99,22 +148,27 @@ pub fn head_rotation (
     }
 }
 
+/// Walks along the ground under the [`GroundProbe`] of the same entity, if any supports.
 #[derive(Component, Clone, Copy)]
 pub struct BasicLocomotion {
     pub speed: f32,
+    /// In meters per second squared.
     pub max_acceleration: f32,
 }
 impl Default for BasicLocomotion {
     fn default() -> Self {
-        Self { speed: 4.0, max_acceleration: 2.0 }
+        Self { speed: 4.0, max_acceleration: 120.0 }
     }
 }
 pub fn basic_locomotion (
     mut characters: Query<(&GlobalTransform, &mut Velocity, &AttachedInput<Locomotion>)>,
-    params: Query<(&BasicLocomotion, &Parent)>,
+    params: Query<(&BasicLocomotion, &Parent, Option<(&GroundProbe, &GroundInfo)>)>,
     positional_inputs: Query<&PositionalInput>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
 ) {
-    for (param, parent) in params.iter() {
+    let delta = physics_delta_seconds(&rapier_config, &time);
+    for (param, parent, ground) in params.iter() {
         let Ok((transform, mut velocity, input)) = characters.get_mut(parent.get()) else {
             warn!("Entity not found!");
             continue;
@@ -125,10 +179,15 @@ pub fn basic_locomotion (
         };
         let (_scale, rotation, _translation) = transform.to_scale_rotation_translation();
         let target_velocity = param.speed * rotation.mul_vec3(locomotion.value);
+        // along slopes, instead of launching off or sliding down
+        let target_velocity = match ground {
+            Some((probe, GroundInfo { hit: Some(hit) })) if probe.supports(hit) => hit.project(target_velocity),
+            _ => target_velocity,
+        };
         if 0.0 < target_velocity.length() {
             let target_direction = target_velocity.normalize();
             let speed_diff = target_velocity.length() - velocity.linvel.dot(target_direction);
-            let linvel = velocity.linvel + speed_diff.clamp(0.0, param.max_acceleration) * target_direction;
+            let linvel = velocity.linvel + speed_diff.clamp(0.0, delta * param.max_acceleration) * target_direction;
             // avoid false change detection
             if velocity.linvel != linvel {
                 velocity.linvel = linvel;
@@ -141,18 +200,22 @@ pub fn basic_locomotion (
 #[derive(Component, Clone, Copy)]
 pub struct AirborneLocomotion {
     pub speed: f32,
+    /// In meters per second squared.
     pub max_acceleration: f32,
 }
 impl Default for AirborneLocomotion {
     fn default() -> Self {
-        Self { speed: 2.0, max_acceleration: 0.4 }
+        Self { speed: 2.0, max_acceleration: 24.0 }
     }
 }
 pub fn airborne_locomotion (
     mut characters: Query<(&GlobalTransform, &mut Velocity, &AttachedInput<Locomotion>)>,
     params: Query<(&AirborneLocomotion, &Parent)>,
     positional_inputs: Query<&PositionalInput>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
 ) {
+    let delta = physics_delta_seconds(&rapier_config, &time);
     for (param, parent) in params.iter() {
         let Ok((transform, mut velocity, input)) = characters.get_mut(parent.get()) else {
             warn!("Entity not found!");
@@ -164,7 +227,7 @@ pub fn airborne_locomotion (
         };
         let (_scale, rotation, _translation) = transform.to_scale_rotation_translation();
         let locomotion_global = rotation.mul_vec3(locomotion.value);
-        let target = param.max_acceleration * Vec2::new(locomotion_global.x, locomotion_global.z);    // xz() swizzling not found in Bevy
+        let target = delta * param.max_acceleration * Vec2::new(locomotion_global.x, locomotion_global.z);    // xz() swizzling not found in Bevy
         if 0.0 < target.length() {
             let horizontal_velocity = Vec2::new(velocity.linvel.x, velocity.linvel.z);
             let target_direction = target.normalize();
@@ -182,3 +245,101 @@ pub fn airborne_locomotion (
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{input::InputPlugin, window::WindowPlugin, time::TimeUpdateStrategy, render::mesh::MeshPlugin, scene::ScenePlugin};
+    use seldom_state::prelude::*;
+
+    use super::*;
+    use crate::{
+        cascade_input::{CascadeInputPlugin, button::ButtonInput},
+        character_control::{
+            CharacterControlPlugin,
+            backend::CharacterBackend,
+            crouch::CharacterCapsule,
+            grounded_states::{GroundedStateMachine, GroundedStateMachineBundle, JumpAssist},
+            stamina::Stamina,
+        },
+        global_settings::PHYSICS_TIMESTEP,
+        util::state_machine::StateMachineUtilPlugin,
+    };
+
+    /// Walks from 0.5 s, jumps at 1.0 s and releases at 1.3 s, on the timestep of the game.
+    /// Returns the positions every 0.2 s, over 3 s.
+    fn trajectory(backend: CharacterBackend, frame_rate: u32) -> Vec<Vec3> {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), TransformPlugin, HierarchyPlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins((RapierPhysicsPlugin::<NoUserData>::default(), StateMachinePlugin, StateMachineUtilPlugin, CascadeInputPlugin, CharacterControlPlugin))
+            .insert_resource(RapierConfiguration { timestep_mode: PHYSICS_TIMESTEP, ..default() })
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / frame_rate as f64)));
+        let world = &mut app.world;
+        world.spawn((TransformBundle::from_transform(Transform::from_xyz(0.0, -0.5, 0.0)), Collider::cuboid(100.0, 0.5, 100.0)));
+        let jump = world.spawn(ButtonInput::default()).id();
+        let crouch = world.spawn(ButtonInput::default()).id();
+        let sprint = world.spawn(ButtonInput::default()).id();
+        let locomotion = world.spawn(PositionalInput::default()).id();
+        let machine = GroundedStateMachine::default_machine(jump, crouch, sprint, JumpAssist::default());
+        let machine = world.spawn(GroundedStateMachineBundle {
+            state_machine: GroundedStateMachine::set_state_components_sample(machine),
+            sensor: Collider::ball(0.2),
+            transform: TransformBundle::from_transform(Transform::from_xyz(0.0, -1.7, 0.0)),
+            ..default()
+        }).id();
+        let capsule = CharacterCapsule::new(1.5, 0.3);
+        let body = world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 1.8, 0.0)),
+            backend,
+            Velocity::default(),
+            LockedAxes::ROTATION_LOCKED,
+            capsule.collider(),
+            capsule,
+            Friction::coefficient(0.98),
+            Stamina::new(100.0),
+            AttachedInput::<Locomotion>::new(locomotion),
+        )).add_child(machine).id();
+
+        // the first frame has no duration
+        app.update();
+        let frames_at = |seconds: f32| (seconds * frame_rate as f32).round() as usize;
+        let mut positions = vec![];
+        for frame in 0..frames_at(3.0) {
+            if frame == frames_at(0.5) {
+                app.world.get_mut::<PositionalInput>(locomotion).unwrap().value = Vec3::X;
+            }
+            if frame == frames_at(1.0) {
+                app.world.get_mut::<ButtonInput>(jump).unwrap().press();
+            }
+            if frame == frames_at(1.3) {
+                app.world.get_mut::<ButtonInput>(jump).unwrap().release();
+            }
+            app.update();
+            if (frame + 1) % frames_at(0.2) == 0 {
+                positions.push(app.world.get::<Transform>(body).unwrap().translation);
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn trajectories_are_independent_of_frame_rate() {
+        for backend in [CharacterBackend::Dynamic, CharacterBackend::Kinematic] {
+            let reference = trajectory(backend, 60);
+            assert!(1.0 < reference.iter().map(|position| position.y).fold(f32::MIN, f32::max) - 1.8, "{:?} not jumped", backend);
+            for frame_rate in [30, 240] {
+                let positions = trajectory(backend, frame_rate);
+                assert_eq!(positions.len(), reference.len());
+                // walked for half a second of wall-clock time, not slowed down with the frame rate
+                let walked = positions[4].x;
+                assert!((walked - 0.5 * BasicLocomotion::default().speed).abs() < 0.2, "{:?} at {} Hz walked {} m in 0.5 s", backend, frame_rate, walked);
+                for (time, (position, expected)) in positions.iter().zip(reference.iter()).enumerate() {
+                    // ground friction slows walking within each step, so shorter steps walk slightly faster
+                    assert!(position.distance(*expected) < 0.05 + 0.02 * expected.x, "{:?} at {} Hz, {} s: {} instead of {}", backend, frame_rate, 0.2 * (time + 1) as f32, position, expected);
+                }
+            }
+        }
+    }
+}
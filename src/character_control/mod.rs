@@ -3,9 +3,17 @@ use std::marker::PhantomData;
 use bevy::prelude::*;
 
 use locomotion_system::LocomotionSystemPlugin;
+use crouch::CrouchPlugin;
+use ground::GroundProbePlugin;
+use backend::CharacterBackendPlugin;
+use stamina::StaminaPlugin;
 
 pub mod grounded_states;
 pub mod locomotion_system;
+pub mod crouch;
+pub mod ground;
+pub mod backend;
+pub mod stamina;
 
 
 #[derive(Debug, Component)]
@@ -43,6 +51,6 @@ pub struct Jump;
 pub struct CharacterControlPlugin;
 impl Plugin for CharacterControlPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((LocomotionSystemPlugin, ));
+        app.add_plugins((LocomotionSystemPlugin, CrouchPlugin, GroundProbePlugin, CharacterBackendPlugin, StaminaPlugin, ));
     }
 }
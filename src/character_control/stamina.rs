@@ -0,0 +1,176 @@
+//! Stamina of characters, drained by effortful states and regenerated while resting.
+//!
+//! States drain the [`Stamina`] of their parent with [`DrainStamina`] over time, or with [`StaminaCost`] once,
+//! like locomotion parameters. Time is the time the physics advanced, as for the locomotion.
+//! Emptying it makes the character exhausted until it recovers past a threshold,
+//! which state machines check with [`ExhaustedTrigger`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use seldom_state::trigger::BoolTrigger;
+
+use super::locomotion_system::physics_delta_seconds;
+
+
+pub struct StaminaPlugin;
+impl Plugin for StaminaPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, update_stamina)
+        ;
+    }
+}
+
+
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct Stamina {
+    pub max: f32,
+    /// Per second while resting.
+    pub regen_rate: f32,
+    /// Seconds of rest before regenerating.
+    pub regen_delay: f32,
+    /// Fraction of `max` to recover from exhaustion.
+    pub recover_threshold: f32,
+    current: f32,
+    /// Seconds since the last drain.
+    rest: f32,
+    exhausted: bool,
+}
+impl Stamina {
+    pub fn new(max: f32) -> Self {
+        Self {
+            max: max,
+            regen_rate: 20.0,
+            regen_delay: 1.0,
+            recover_threshold: 0.3,
+            current: max,
+            rest: 0.0,
+            exhausted: false,
+        }
+    }
+    pub fn current(&self) -> f32 { self.current }
+    /// Current over max, for gauges.
+    pub fn fraction(&self) -> f32 {
+        if 0.0 < self.max {self.current / self.max} else {0.0}
+    }
+    /// Emptied and not recovered yet.
+    pub fn is_exhausted(&self) -> bool { self.exhausted }
+
+    fn drain(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+        self.rest = 0.0;
+        if self.current <= 0.0 {
+            self.exhausted = true;
+        }
+    }
+    fn regenerate(&mut self, delta: f32) {
+        self.rest += delta;
+        if self.rest < self.regen_delay {
+            return;
+        }
+        self.current = (self.current + delta * self.regen_rate).min(self.max);
+        if self.recover_threshold * self.max <= self.current {
+            self.exhausted = false;
+        }
+    }
+}
+
+/// Parent loses stamina while this is on.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct DrainStamina {
+    /// Per second.
+    pub rate: f32,
+}
+
+/// Parent loses the amount of stamina once, when this is added, such as on entering a jump.
+#[derive(Debug, Component, Clone, Copy, PartialEq)]
+pub struct StaminaCost {
+    pub amount: f32,
+}
+
+fn update_stamina(
+    mut characters: Query<(Entity, &mut Stamina)>,
+    params: Query<(&DrainStamina, &Parent)>,
+    costs: Query<(&StaminaCost, &Parent), Added<StaminaCost>>,
+    rapier_config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let delta = physics_delta_seconds(&rapier_config, &time);
+    let mut drains: HashMap<Entity, f32> = HashMap::new();
+    for (drain, parent) in params.iter() {
+        *drains.entry(parent.get()).or_default() += delta * drain.rate;
+    }
+    for (cost, parent) in costs.iter() {
+        *drains.entry(parent.get()).or_default() += cost.amount;
+    }
+    for (character, mut stamina) in characters.iter_mut() {
+        let amount = drains.get(&character).copied().unwrap_or(0.0);
+        let mut next = *stamina;
+        if 0.0 < amount {
+            next.drain(amount);
+        } else {
+            next.regenerate(delta);
+        }
+        // avoid false change detection
+        if *stamina != next {
+            *stamina = next;
+        }
+    }
+}
+
+
+/// While the parent is exhausted, false for parents without [`Stamina`].
+#[derive(Clone, Copy)]
+pub struct ExhaustedTrigger;
+impl BoolTrigger for ExhaustedTrigger {
+    type Param<'w, 's> = (Query<'w, 's, &'static Parent>, Query<'w, 's, &'static Stamina>);
+    fn trigger(
+        &self,
+        entity: Entity,
+        (parents, staminas): Self::Param<'_, '_>,
+    ) -> bool {
+        let Ok(parent) = parents.get(entity) else {
+            warn!("Parent not found!");
+            return false;
+        };
+        staminas.get(parent.get()).map_or(false, |stamina| stamina.is_exhausted())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::global_settings::PHYSICS_TIMESTEP;
+
+    /// Drains for a second with a cost at the start, and returns the stamina left.
+    fn stamina_after_a_second(frame_rate: u32) -> f32 {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, StaminaPlugin))
+            .insert_resource(RapierConfiguration { timestep_mode: PHYSICS_TIMESTEP, ..default() })
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1.0 / frame_rate as f64)));
+        let character = app.world.spawn(Stamina::new(100.0)).id();
+        // the first frame has no duration
+        app.update();
+        app.world.spawn((DrainStamina { rate: 20.0 }, StaminaCost { amount: 10.0 })).set_parent(character);
+        for _ in 0..frame_rate {
+            app.update();
+        }
+        app.world.get::<Stamina>(character).unwrap().current()
+    }
+
+    #[test]
+    fn drain_is_independent_of_frame_rate() {
+        for frame_rate in [30, 60, 240] {
+            let current = stamina_after_a_second(frame_rate);
+            assert!((current - 70.0).abs() < 1e-3, "{} left at {} Hz", current, frame_rate);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Locks the cursor while playing, and pauses when the window loses focus.
+//!
+//! The cursor is grabbed and hidden on entering the playing state, and released on entering the paused state.
+//! [`CursorLocked`] tells mouse-mapped sticks whether the motion belongs to the game.
+
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, CursorGrabMode, WindowFocused},
+};
+
+use crate::cascade_input::{CascadeInputSet, axis::CursorLocked};
+
+
+pub struct CursorLockPlugin<S: States> {
+    /// State to lock the cursor in.
+    pub playing: S,
+    /// State to release the cursor in, and to enter on focus loss.
+    pub paused: S,
+}
+impl<S: States> Plugin for CursorLockPlugin<S> {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(CursorLockStates { playing: self.playing.clone(), paused: self.paused.clone() })
+            .insert_resource(CursorLocked(false))
+            .add_systems(OnEnter(self.playing.clone()), grab_cursor)
+            .add_systems(OnEnter(self.paused.clone()), release_cursor)
+            // pauses before the state transition of the frame
+            .add_systems(PreUpdate, (
+                pause_on_focus_lost::<S>.run_if(in_state(self.playing.clone())),
+                update_cursor_locked::<S>,
+            ).before(CascadeInputSet::Begin))
+        ;
+    }
+}
+
+#[derive(Resource)]
+struct CursorLockStates<S: States> {
+    playing: S,
+    paused: S,
+}
+
+
+fn grab_cursor(
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for mut window in windows.iter_mut() {
+        // falls back to confined where locking is unsupported
+        window.cursor.grab_mode = CursorGrabMode::Locked;
+        window.cursor.visible = false;
+    }
+}
+fn release_cursor(
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for mut window in windows.iter_mut() {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
+    }
+}
+
+fn pause_on_focus_lost<S: States>(
+    mut focus_events: EventReader<WindowFocused>,
+    primary_windows: Query<(), With<PrimaryWindow>>,
+    states: Res<CursorLockStates<S>>,
+    mut next_state: ResMut<NextState<S>>,
+) {
+    if focus_events.iter().any(|event| !event.focused && primary_windows.contains(event.window)) {
+        next_state.set(states.paused.clone());
+    }
+}
+
+fn update_cursor_locked<S: States>(
+    mut cursor_locked: ResMut<CursorLocked>,
+    mut focus_events: EventReader<WindowFocused>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    state: Res<State<S>>,
+    states: Res<CursorLockStates<S>>,
+) {
+    // the window may not reflect the focus yet
+    let lost = focus_events.iter().any(|event| !event.focused);
+    let locked = !lost && *state.get() == states.playing && windows.iter().any(|window|
+        window.focused && window.cursor.grab_mode != CursorGrabMode::None
+    );
+    // avoid false change detection
+    if cursor_locked.0 != locked {
+        cursor_locked.0 = locked;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::{input::{InputPlugin, mouse::MouseMotion}, window::{WindowPlugin, CursorMoved}};
+
+    use super::*;
+    use crate::cascade_input::{CascadeInputPlugin, axis::{StickInput, MappedMouse}};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, States)]
+    enum TestStates {
+        #[default]
+        Playing,
+        Paused,
+    }
+
+    fn app() -> (App, Entity) {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, WindowPlugin::default(), CascadeInputPlugin))
+            .add_state::<TestStates>()
+            .add_plugins(CursorLockPlugin { playing: TestStates::Playing, paused: TestStates::Paused });
+        app.update();
+        let window = app.world.query_filtered::<Entity, With<PrimaryWindow>>().single(&app.world);
+        (app, window)
+    }
+    fn cursor(app: &App, window: Entity) -> (CursorGrabMode, bool) {
+        let window = app.world.get::<Window>(window).unwrap();
+        (window.cursor.grab_mode, window.cursor.visible)
+    }
+
+    #[test]
+    fn focus_loss_pauses_and_releases_the_cursor() {
+        let (mut app, window) = app();
+        assert_eq!(cursor(&app, window), (CursorGrabMode::Locked, false));
+        app.update();
+        assert!(app.world.resource::<CursorLocked>().0);
+
+        app.world.send_event(WindowFocused { window, focused: false });
+        app.update();
+        assert_eq!(*app.world.resource::<State<TestStates>>().get(), TestStates::Paused);
+        assert_eq!(cursor(&app, window), (CursorGrabMode::None, true));
+        assert!(!app.world.resource::<CursorLocked>().0);
+
+        app.world.resource_mut::<NextState<TestStates>>().set(TestStates::Playing);
+        app.world.send_event(WindowFocused { window, focused: true });
+        app.update();
+        app.update();
+        assert_eq!(cursor(&app, window), (CursorGrabMode::Locked, false));
+        assert!(app.world.resource::<CursorLocked>().0);
+    }
+
+    #[test]
+    fn mouse_motion_is_dropped_while_unlocked() {
+        let (mut app, window) = app();
+        let stick = app.world.spawn((StickInput::default(), MappedMouse::new(Vec2::ONE))).id();
+        app.update();
+        let look = |app: &mut App| {
+            app.world.send_event(MouseMotion { delta: Vec2::new(10.0, 0.0) });
+            app.update();
+            app.world.get::<StickInput>(stick).unwrap().value
+        };
+        assert_eq!(look(&mut app), Vec2::new(10.0, 0.0));
+
+        // motion arriving with the focus loss
+        app.world.send_event(WindowFocused { window, focused: false });
+        assert_eq!(look(&mut app), Vec2::ZERO);
+        assert_eq!(look(&mut app), Vec2::ZERO);
+
+        app.world.resource_mut::<NextState<TestStates>>().set(TestStates::Playing);
+        app.world.send_event(WindowFocused { window, focused: true });
+        app.update();
+        app.update();
+        assert_eq!(look(&mut app), Vec2::new(10.0, 0.0));
+    }
+
+    #[test]
+    fn cursor_jump_on_locking_again_is_ignored() {
+        let (mut app, window) = app();
+        let mut mouse = MappedMouse::new(Vec2::ONE);
+        mouse.raw = false;
+        let stick = app.world.spawn((StickInput::default(), mouse)).id();
+        app.update();
+        let move_cursor = |app: &mut App, x: f32| {
+            app.world.send_event(CursorMoved { window, position: Vec2::new(x, 100.0) });
+            app.update();
+            app.world.get::<StickInput>(stick).unwrap().value
+        };
+        move_cursor(&mut app, 100.0);
+        assert_eq!(move_cursor(&mut app, 110.0), Vec2::new(10.0, 0.0));
+
+        app.world.send_event(WindowFocused { window, focused: false });
+        assert_eq!(move_cursor(&mut app, 300.0), Vec2::ZERO);
+        assert_eq!(move_cursor(&mut app, 500.0), Vec2::ZERO);
+
+        app.world.resource_mut::<NextState<TestStates>>().set(TestStates::Playing);
+        app.world.send_event(WindowFocused { window, focused: true });
+        app.update();
+        app.update();
+        // the first position after locking is only the start
+        assert_eq!(move_cursor(&mut app, 120.0), Vec2::ZERO);
+        assert_eq!(move_cursor(&mut app, 125.0), Vec2::new(5.0, 0.0));
+    }
+}
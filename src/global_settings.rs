@@ -1,5 +1,11 @@
 
-use bevy_rapier3d::geometry::Group;
+use bevy_rapier3d::{geometry::Group, plugin::TimestepMode};
+
+use crate::cascade_input::context::InputContext;
+
+/// Steps the physics by the frame time, so that the game runs in real time down to 20 FPS,
+/// instead of slowing down below 60 FPS as by default.
+pub const PHYSICS_TIMESTEP: TimestepMode = TimestepMode::Variable { max_dt: 1.0 / 20.0, time_scale: 1.0, substeps: 1 };
 
 pub struct NamedCollisionGroup;
 impl NamedCollisionGroup {
@@ -19,3 +25,9 @@ pub enum CameraOrder {
     _Main,
     Ui2d,
 }
+
+pub struct NamedInputContext;
+impl NamedInputContext {
+    pub const GAMEPLAY: InputContext = InputContext("gameplay");
+    pub const MENU: InputContext = InputContext("menu");
+}
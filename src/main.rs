@@ -2,7 +2,7 @@
 use ai::AiPlugin;
 use attack::{AttackPlugin, HitArea};
 use bevior_tree::BehaviorTreePlugin;
-use bevy::{prelude::*, input::keyboard::KeyboardInput};
+use bevy::prelude::*;
 #[cfg(not(target_family="wasm"))]
 use bevy::{
     pbr::{
@@ -12,26 +12,40 @@ use bevy::{
     core_pipeline::experimental::taa::{TemporalAntiAliasBundle, TemporalAntiAliasPlugin},
 };
 use bevy_rapier3d::prelude::*;
-use global_settings::NamedCollisionGroup;
+use global_settings::{NamedCollisionGroup, NamedInputContext, PHYSICS_TIMESTEP};
 use projectile_spawner::{
     simple_ball,
     ProjectileSpawnerPlugin,
 };
 use seldom_state::prelude::*;
 
-use cascade_input::{CascadeInputPlugin, axis::{PositionalInput, RotationalInput}};
+use cascade_input::{
+    CascadeInputPlugin,
+    axis::{PositionalInput, RotationalInput},
+    button::{ButtonInput, MappedDeviceButton, DeviceButtonCode},
+    context::{InputContextStack, InputContextLayer},
+};
+#[cfg(debug_assertions)]
+use cascade_input::debug::CascadeDebugPlugin;
+#[cfg(not(target_family="wasm"))]
+use cascade_input::record::{InputRecorder, InputRecording, InputReplay};
 use character_control::{
-    grounded_states::{GroundedStateMachineBundle, GroundedStateMachine, Grounded},
+    grounded_states::{GroundedStateMachineBundle, GroundedStateMachine, Grounded, JumpAssist},
+    crouch::{CharacterCapsule, CharacterFriction},
+    backend::CharacterBackend,
+    stamina::Stamina,
     CharacterControlPlugin, AttachedInput, Locomotion, HeadAttitude, Jump, Rotation, HeadBundle,
 };
-use player_input::{PlayerInputPlugin, create_player_inputs};
+use player_input::{PlayerInputPlugin, create_player_inputs, binding::BindingProfile};
 use util::{state_machine::StateMachineUtilPlugin, ecs::EcsUtilPlugin};
 use ui::GameUiPlugin;
+use cursor_lock::CursorLockPlugin;
 
 mod util;
 mod global_settings;
 mod cascade_input;
 mod ui;
+mod cursor_lock;
 mod character_control;
 mod player_input;
 mod attack;
@@ -40,7 +54,8 @@ mod ai;
 
 fn main() {
     let mut app = App::new();
-    setup_app(&mut app)
+    setup_app(&mut app);
+    setup_input_recording(&mut app)
         .add_plugins((
             CascadeInputPlugin, EcsUtilPlugin, StateMachineUtilPlugin,
             CharacterControlPlugin, PlayerInputPlugin, AttackPlugin, ProjectileSpawnerPlugin,
@@ -49,12 +64,22 @@ fn main() {
         ))
         .insert_resource(Msaa::Off)
         .add_state::<GameStates>()
-        .add_systems(Startup, setup)
-        .add_systems(Last, pause.run_if(in_state(GameStates::MainGame)))
-        .add_systems(Last, unpause.run_if(in_state(GameStates::Pause)))
+        .add_plugins(CursorLockPlugin { playing: GameStates::MainGame, paused: GameStates::Pause })
+        .insert_resource(InputContextStack::new(vec![
+            InputContextLayer { context: NamedInputContext::GAMEPLAY, exclusive: false },
+        ]))
+        .add_systems(Startup, (setup, setup_menu_inputs))
+        .add_systems(Update, pause.run_if(in_state(GameStates::MainGame)))
+        .add_systems(Update, unpause.run_if(in_state(GameStates::Pause)))
+        .add_systems(OnEnter(GameStates::Pause), push_menu_context)
+        .add_systems(OnExit(GameStates::Pause), remove_menu_context)
     ;
+    // inspecting the cascade is for development only
+    #[cfg(debug_assertions)]
+    app.add_plugins(CascadeDebugPlugin { context: NamedInputContext::GAMEPLAY });
     app
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .insert_resource(RapierConfiguration { timestep_mode: PHYSICS_TIMESTEP, ..default() })
         .configure_sets(PostUpdate, (
             PhysicsSet::SyncBackend.run_if(in_state(GameStates::MainGame)),
             PhysicsSet::SyncBackendFlush.run_if(in_state(GameStates::MainGame)),
@@ -80,7 +105,14 @@ fn main() {
 
 #[cfg(not(target_family="wasm"))]
 fn setup_app(app: &mut App) -> &mut App {
-        app.add_plugins((DefaultPlugins, TemporalAntiAliasPlugin))
+        app.add_plugins((
+            DefaultPlugins.set(AssetPlugin {
+                // rebuilds virtual gamepads on edit
+                watch_for_changes: bevy::asset::ChangeWatcher::with_delay(std::time::Duration::from_millis(200)),
+                ..default()
+            }),
+            TemporalAntiAliasPlugin,
+        ))
 }
 
 #[cfg(target_family="wasm")]
@@ -88,6 +120,26 @@ fn setup_app(app: &mut App) -> &mut App {
         app.add_plugins(DefaultPlugins)
 }
 
+/// Records inputs to `INPUT_RECORD` path, or replays from `INPUT_REPLAY` path.
+#[cfg(not(target_family="wasm"))]
+fn setup_input_recording(app: &mut App) -> &mut App {
+    if let Ok(path) = std::env::var("INPUT_REPLAY") {
+        match InputRecording::load(path.as_ref()) {
+            Ok(recording) => {app.insert_resource(InputReplay::new(recording));},
+            Err(err) => error!("Failed to load input recording {:?}: {}", path, err),
+        }
+    }
+    if let Ok(path) = std::env::var("INPUT_RECORD") {
+        app.insert_resource(InputRecorder::new(Some(path.into())));
+    }
+    app
+}
+
+#[cfg(target_family="wasm")]
+fn setup_input_recording(app: &mut App) -> &mut App {
+    app
+}
+
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default, States)]
 enum GameStates {
@@ -96,23 +148,54 @@ enum GameStates {
     Pause,
 }
 
+#[derive(Component)]
+struct PauseButton;
+#[derive(Component)]
+struct ResumeButton;
+
+fn setup_menu_inputs(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        Name::new("Pause"),
+        NamedInputContext::GAMEPLAY,
+        ButtonInput::default(),
+        MappedDeviceButton::new(DeviceButtonCode::Key(KeyCode::Escape)),
+        PauseButton,
+    ));
+    commands.spawn((
+        Name::new("Resume"),
+        NamedInputContext::MENU,
+        ButtonInput::default(),
+        MappedDeviceButton::new(DeviceButtonCode::Key(KeyCode::Escape)),
+        ResumeButton,
+    ));
+}
+
+fn push_menu_context(
+    mut contexts: ResMut<InputContextStack>,
+) {
+    contexts.push(NamedInputContext::MENU, true);
+}
+fn remove_menu_context(
+    mut contexts: ResMut<InputContextStack>,
+) {
+    contexts.remove(NamedInputContext::MENU);
+}
+
 fn pause (
-    mut keyboard_input_events: EventReader<KeyboardInput>,
+    buttons: Query<&ButtonInput, With<PauseButton>>,
     mut state: ResMut<NextState<GameStates>>
 ) {
-    if keyboard_input_events.iter().any(|event|
-        (event.key_code, event.state) == (Some(KeyCode::Escape), bevy::input::ButtonState::Pressed)
-    ) {
+    if buttons.iter().any(|button| button.just_pressed()) {
         state.set(GameStates::Pause);
     }
 }
 fn unpause (
-    mut keyboard_input_events: EventReader<KeyboardInput>,
+    buttons: Query<&ButtonInput, With<ResumeButton>>,
     mut state: ResMut<NextState<GameStates>>
 ) {
-    if keyboard_input_events.iter().any(|event|
-        (event.key_code, event.state) == (Some(KeyCode::Escape), bevy::input::ButtonState::Pressed)
-    ) {
+    if buttons.iter().any(|button| button.just_pressed()) {
         state.set(GameStates::MainGame);
     }
 }
@@ -128,6 +211,8 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    binding_profile: Res<BindingProfile>,
+    asset_server: Res<AssetServer>,
 ) {
     // plane
     commands
@@ -192,15 +277,15 @@ fn setup(
             ..default()
         })
         .insert(Velocity::default())
-        .insert(RigidBody::Dynamic)
+        .insert(CharacterBackend::Dynamic)
         .insert(LockedAxes::ROTATION_LOCKED)
-        .insert(Collider::capsule_y(1.5, 0.3))
-        .insert(Friction::coefficient(0.98))
+        .insert((CharacterCapsule::new(1.5, 0.3), CharacterCapsule::new(1.5, 0.3).collider()))
+        .insert((CharacterFriction { friction: Friction::coefficient(0.98) }, Friction::coefficient(0.98)))
         .insert(CollisionGroups::new(NamedCollisionGroup::CHARACTER, NamedCollisionGroup::ALL))
-        .insert(KinematicCharacterController {..default()})
+        .insert(Stamina::new(100.0))
     ;
     //controller
-    let controller = create_player_inputs(&mut player_builder);
+    let controller = create_player_inputs(&mut player_builder, asset_server.load(binding_profile.layout.asset_path()));
     player_builder.insert((
         AttachedInput::<Locomotion>::new(controller.locomotion),
         AttachedInput::<Rotation>::new(controller.rotation),
@@ -220,7 +305,7 @@ fn setup(
                 }
             ));
         });
-        let grounded_state_machine = GroundedStateMachine::default_machine(controller.jump);
+        let grounded_state_machine = GroundedStateMachine::default_machine(controller.jump, controller.crouch, controller.sprint, JumpAssist::default());
         let grounded_state_machine = GroundedStateMachine::set_state_components_sample(grounded_state_machine);
         player.spawn(GroundedStateMachineBundle {
             state_machine: grounded_state_machine,
@@ -0,0 +1,382 @@
+//! Named actions of the player and devices bound to them.
+//!
+//! [`BindingProfile`] is loaded from the user config directory on start-up,
+//! and saved back whenever it changes after that.
+//! A file failing to load is left as is for the user to fix, and changes are not saved over it.
+//! Modifying the resource rewrites the device-mapped inputs spawned by [`create_player_inputs`](super::create_player_inputs).
+
+use std::{collections::BTreeMap, fmt, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use crate::cascade_input::{
+    CascadeInputSet,
+    button::{DeviceButtonCode, MappedDeviceButton},
+    axis::MappedMouse,
+};
+
+use super::{PlayerInputLayout, ScaledByTime, TargetRotation};
+
+
+pub struct BindingProfilePlugin;
+impl Plugin for BindingProfilePlugin {
+    fn build(&self, app: &mut App) {
+        let (profile, file) = BindingProfile::load_or_default();
+        if let Some(file) = file {
+            app.insert_resource(BindingProfileFile(file));
+        }
+        app
+            .insert_resource(profile)
+            .add_systems(PreUpdate,
+                apply_binding_profile
+                .run_if(resource_changed::<BindingProfile>())
+                .before(CascadeInputSet::Begin)
+            )
+            .add_systems(Last, save_binding_profile
+                .run_if(resource_exists::<BindingProfileFile>())
+                .run_if(binding_profile_modified)
+            )
+        ;
+    }
+}
+
+/// File [`BindingProfile`] is saved to on change.
+/// Absent while there is nowhere to save, or the file failed to load.
+#[derive(Debug, Resource, Clone, PartialEq, Eq)]
+pub struct BindingProfileFile(pub PathBuf);
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerAction {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    WalkToggle,
+    Jump,
+    Fire,
+    Reload,
+    Crouch,
+    Sprint,
+}
+
+/// Marks the button to be rewritten by [`BindingProfile`].
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq)]
+pub struct BoundAction(pub PlayerAction);
+
+/// Marks the look stick to be rewritten by [`BindingProfile`].
+#[derive(Debug, Component, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BoundLook;
+
+
+#[derive(Debug, Resource, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BindingProfile {
+    pub layout: PlayerInputLayout,
+    pub buttons: BTreeMap<PlayerAction, DeviceButtonCode>,
+    /// Also read from `mouse_sensitivity` in radians per count, saved before these settings.
+    #[serde(default, alias = "mouse_sensitivity", deserialize_with = "deserialize_mouse_look")]
+    pub mouse_look: MouseLookSettings,
+    /// Radians per second at full tilt.
+    pub gamepad_look_speed: Vec2,
+    /// Lowest and highest pitch of the head, in degrees.
+    #[serde(default = "default_pitch_range")]
+    pub pitch_range: (f32, f32),
+    /// Walk while the walk button is held, instead of toggling.
+    #[serde(default)]
+    pub hold_to_walk: bool,
+}
+fn default_pitch_range() -> (f32, f32) {
+    (-90.0, 90.0)
+}
+
+/// Mouse look independent of the frame rate and the DPI.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseLookSettings {
+    /// Degrees turned per mouse count.
+    pub degrees_per_count: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    /// Extra gain per speed in counts per second, `0.0` for none.
+    pub acceleration: f32,
+    /// Seconds for the smoothed speed to reach 63% of a change, `0.0` for none.
+    pub smoothing: f32,
+    /// Bypasses pointer acceleration of OS.
+    pub raw: bool,
+}
+impl Default for MouseLookSettings {
+    fn default() -> Self {
+        Self {
+            degrees_per_count: 0.0458,
+            invert_x: false,
+            invert_y: false,
+            acceleration: 0.0,
+            smoothing: 0.0,
+            raw: true,
+        }
+    }
+}
+/// Either the settings or the sensitivity saved before them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedMouseLook {
+    Settings(MouseLookSettings),
+    Sensitivity(Vec2),
+}
+fn deserialize_mouse_look<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<MouseLookSettings, D::Error> {
+    Ok(match SavedMouseLook::deserialize(deserializer)? {
+        SavedMouseLook::Settings(settings) => settings,
+        SavedMouseLook::Sensitivity(sensitivity) => MouseLookSettings {
+            degrees_per_count: sensitivity.x.abs().to_degrees(),
+            invert_x: sensitivity.x < 0.0,
+            invert_y: sensitivity.y < 0.0,
+            ..default()
+        },
+    })
+}
+
+impl MouseLookSettings {
+    /// Centimeters of mouse travel to turn around, with the mouse of `dpi`.
+    pub fn cm_per_360(&self, dpi: f32) -> f32 {
+        360.0 / (self.degrees_per_count * dpi) * 2.54
+    }
+    pub fn set_cm_per_360(&mut self, cm: f32, dpi: f32) {
+        self.degrees_per_count = 360.0 / (cm / 2.54 * dpi);
+    }
+    /// Radians per count, negated on inverted axes.
+    pub fn sensitivity(&self) -> Vec2 {
+        let radians = self.degrees_per_count.to_radians();
+        Vec2::new(
+            if self.invert_x {-radians} else {radians},
+            if self.invert_y {-radians} else {radians},
+        )
+    }
+    pub fn apply(&self, mouse: &mut MappedMouse) {
+        mouse.sensitivity = self.sensitivity();
+        mouse.acceleration = self.acceleration;
+        mouse.smoothing = self.smoothing;
+        mouse.raw = self.raw;
+    }
+}
+
+impl Default for BindingProfile {
+    fn default() -> Self {
+        Self::default_for(PlayerInputLayout::default())
+    }
+}
+impl BindingProfile {
+    pub fn default_for(layout: PlayerInputLayout) -> Self {
+        let buttons = match layout {
+            PlayerInputLayout::KeyboardMouse => vec![
+                (PlayerAction::MoveForward, DeviceButtonCode::Key(KeyCode::W)),
+                (PlayerAction::MoveBack, DeviceButtonCode::Key(KeyCode::S)),
+                (PlayerAction::MoveLeft, DeviceButtonCode::Key(KeyCode::A)),
+                (PlayerAction::MoveRight, DeviceButtonCode::Key(KeyCode::D)),
+                (PlayerAction::WalkToggle, DeviceButtonCode::Key(KeyCode::C)),
+                (PlayerAction::Jump, DeviceButtonCode::Key(KeyCode::Space)),
+                (PlayerAction::Fire, DeviceButtonCode::Mouse(MouseButton::Left)),
+                (PlayerAction::Reload, DeviceButtonCode::Key(KeyCode::R)),
+                (PlayerAction::Crouch, DeviceButtonCode::Key(KeyCode::ControlLeft)),
+                (PlayerAction::Sprint, DeviceButtonCode::Key(KeyCode::ShiftLeft)),
+            ],
+            PlayerInputLayout::Gamepad => vec![
+                (PlayerAction::WalkToggle, DeviceButtonCode::Gamepad(GamepadButtonType::LeftThumb)),
+                (PlayerAction::Jump, DeviceButtonCode::Gamepad(GamepadButtonType::South)),
+                (PlayerAction::Fire, DeviceButtonCode::Gamepad(GamepadButtonType::RightTrigger2)),
+                (PlayerAction::Reload, DeviceButtonCode::Gamepad(GamepadButtonType::West)),
+                (PlayerAction::Crouch, DeviceButtonCode::Gamepad(GamepadButtonType::East)),
+                (PlayerAction::Sprint, DeviceButtonCode::Gamepad(GamepadButtonType::LeftTrigger)),
+            ],
+        };
+        Self {
+            layout,
+            buttons: buttons.into_iter().collect(),
+            mouse_look: MouseLookSettings::default(),
+            gamepad_look_speed: Vec2::new(3.0, 2.0),
+            pitch_range: default_pitch_range(),
+            hold_to_walk: false,
+        }
+    }
+
+    /// Pitch range in radians.
+    pub fn pitch_range_radians(&self) -> (f32, f32) {
+        (self.pitch_range.0.to_radians(), self.pitch_range.1.to_radians())
+    }
+
+    pub fn code(&self, action: PlayerAction) -> Option<DeviceButtonCode> {
+        self.buttons.get(&action).copied()
+    }
+
+    /// Binds the code to the action, unless other action already uses it.
+    pub fn bind(&mut self, action: PlayerAction, code: DeviceButtonCode) -> Result<(), BindingConflict> {
+        if let Some((&bound, _)) = self.buttons.iter().find(|&(&bound, &bound_code)| bound != action && bound_code == code) {
+            return Err(BindingConflict { code, actions: (bound, action) });
+        }
+        self.buttons.insert(action, code);
+        Ok(())
+    }
+    pub fn unbind(&mut self, action: PlayerAction) -> Option<DeviceButtonCode> {
+        self.buttons.remove(&action)
+    }
+
+    /// Binds the defaults to unbound actions, such as actions added after the profile was saved.
+    /// Defaults already used by other actions are skipped.
+    pub fn bind_missing_defaults(&mut self) {
+        for (action, code) in Self::default_for(self.layout).buttons {
+            if !self.buttons.contains_key(&action) {
+                let _ = self.bind(action, code);
+            }
+        }
+    }
+
+    /// Actions sharing the same code, which may come from hand-edited files.
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let bindings: Vec<(PlayerAction, DeviceButtonCode)> = self.buttons.iter().map(|(&action, &code)| (action, code)).collect();
+        bindings.iter().enumerate().flat_map(|(i, &(action, code))| {
+            bindings[i+1..].iter()
+                .filter(move |&&(_, other_code)| other_code == code)
+                .map(move |&(other, _)| BindingConflict { code, actions: (action, other) })
+        }).collect()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, BindingProfileError> {
+        let text = std::fs::read_to_string(path).map_err(BindingProfileError::Io)?;
+        ron::from_str(&text).map_err(BindingProfileError::Deserialize)
+    }
+    pub fn save(&self, path: &Path) -> Result<(), BindingProfileError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(BindingProfileError::Serialize)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(BindingProfileError::Io)?;
+        }
+        std::fs::write(path, text).map_err(BindingProfileError::Io)
+    }
+
+    /// Loads from the user config directory, falling back to default.
+    /// Also returns the path to save to, `None` if the file exists but failed to load, so as not to overwrite it.
+    pub fn load_or_default() -> (Self, Option<PathBuf>) {
+        let Some(path) = default_path() else {
+            return (Self::default(), None);
+        };
+        if !path.exists() {
+            return (Self::default(), Some(path));
+        }
+        match Self::load(&path) {
+            Ok(mut profile) => {
+                profile.bind_missing_defaults();
+                (profile, Some(path))
+            },
+            Err(err) => {
+                warn!("Failed to load binding profile {:?}, using defaults without saving: {}", path, err);
+                (Self::default(), None)
+            },
+        }
+    }
+}
+
+#[cfg(not(target_family="wasm"))]
+pub fn default_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy_practice").join("bindings.ron"))
+}
+#[cfg(target_family="wasm")]
+pub fn default_path() -> Option<std::path::PathBuf> {
+    None
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub code: DeviceButtonCode,
+    pub actions: (PlayerAction, PlayerAction),
+}
+impl fmt::Display for BindingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is bound to both {:?} and {:?}", self.code, self.actions.0, self.actions.1)
+    }
+}
+
+#[derive(Debug)]
+pub enum BindingProfileError {
+    Io(std::io::Error),
+    Deserialize(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+impl fmt::Display for BindingProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Deserialize(err) => write!(f, "{}", err),
+            Self::Serialize(err) => write!(f, "{}", err),
+        }
+    }
+}
+impl std::error::Error for BindingProfileError {}
+
+
+fn apply_binding_profile(
+    mut commands: Commands,
+    profile: Res<BindingProfile>,
+    mut buttons: Query<(Entity, &BoundAction, Option<&mut MappedDeviceButton>)>,
+    mut mice: Query<&mut MappedMouse, With<BoundLook>>,
+    mut scaled_sticks: Query<&mut ScaledByTime, With<BoundLook>>,
+    mut targets: Query<&mut TargetRotation, With<BoundLook>>,
+) {
+    for conflict in profile.conflicts() {
+        warn!("Binding conflict: {}", conflict);
+    }
+    for (entity, &BoundAction(action), mapped) in buttons.iter_mut() {
+        match (profile.code(action), mapped) {
+            (Some(code), Some(mut mapped)) => {
+                // avoid false change detection
+                if mapped.code != code {
+                    mapped.code = code;
+                }
+            },
+            (Some(code), None) => {
+                commands.entity(entity).insert(MappedDeviceButton::new(code));
+            },
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<MappedDeviceButton>();
+            },
+            (None, None) => {},
+        }
+    }
+    for mut mouse in mice.iter_mut() {
+        let look = &profile.mouse_look;
+        // avoid false change detection
+        if mouse.sensitivity != look.sensitivity() || mouse.acceleration != look.acceleration
+            || mouse.smoothing != look.smoothing || mouse.raw != look.raw
+        {
+            look.apply(&mut mouse);
+        }
+    }
+    for mut target in targets.iter_mut() {
+        let pitch_range = profile.pitch_range_radians();
+        // avoid false change detection
+        if target.pitch_range != pitch_range {
+            target.pitch_range = pitch_range;
+        }
+    }
+    for mut scaled in scaled_sticks.iter_mut() {
+        if scaled.scale != profile.gamepad_look_speed {
+            scaled.scale = profile.gamepad_look_speed;
+        }
+    }
+}
+
+/// Changed since the last run, except for the insertion on start-up, which would rewrite the file just loaded.
+fn binding_profile_modified(
+    profile: Res<BindingProfile>,
+) -> bool {
+    profile.is_changed() && !profile.is_added()
+}
+
+fn save_binding_profile(
+    profile: Res<BindingProfile>,
+    file: Res<BindingProfileFile>,
+) {
+    if let Err(err) = profile.save(&file.0) {
+        warn!("Failed to save binding profile {:?}: {}", file.0, err);
+    }
+}
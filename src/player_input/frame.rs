@@ -0,0 +1,260 @@
+//! Snapshot of the outputs of a virtual gamepad, as the boundary for rollback, lockstep and replays.
+//!
+//! [`InputFrame::capture`] reads the entities of [`PlayerInputs`] after the cascade,
+//! and [`InputFrame::apply`] writes them back before gameplay reads them.
+//! Apply onto outputs without the graph behind, such as of remote players, otherwise the cascade overwrites them.
+//!
+//! The encoding starts with [`InputFrame::VERSION`], and decoding rejects other versions.
+
+use std::fmt;
+
+use bevy::{prelude::*, input::ButtonState};
+use serde::{Serialize, Deserialize};
+
+use crate::cascade_input::{
+    button::ButtonInput,
+    buffer::BufferedPress,
+    axis::{PositionalInput, RotationalInput},
+};
+
+use super::PlayerInputs;
+
+
+/// Button state and the events of the frame, packed into bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonFrame(u8);
+impl ButtonFrame {
+    const PRESSED: u8 = 1 << 0;
+    const JUST_PRESSED: u8 = 1 << 1;
+    const JUST_RELEASED: u8 = 1 << 2;
+    /// Press kept by [`BufferedPress`].
+    const PENDING: u8 = 1 << 3;
+
+    pub fn new(pressed: bool, just_pressed: bool, just_released: bool, pending: bool) -> Self {
+        let bit = |flag: bool, bit: u8| if flag {bit} else {0};
+        Self(
+            bit(pressed, Self::PRESSED) | bit(just_pressed, Self::JUST_PRESSED)
+            | bit(just_released, Self::JUST_RELEASED) | bit(pending, Self::PENDING)
+        )
+    }
+    pub fn pressed(&self) -> bool { self.0 & Self::PRESSED != 0 }
+    pub fn just_pressed(&self) -> bool { self.0 & Self::JUST_PRESSED != 0 }
+    pub fn just_released(&self) -> bool { self.0 & Self::JUST_RELEASED != 0 }
+    pub fn pending(&self) -> bool { self.0 & Self::PENDING != 0 }
+
+    fn capture(button: &ButtonInput, buffer: Option<&BufferedPress>) -> Self {
+        Self::new(button.pressed(), button.just_pressed(), button.just_released(), buffer.map_or(false, |buffer| buffer.is_pending()))
+    }
+    /// Events ending in the state, pressed and released in the frame if both flags.
+    fn events(&self) -> Vec<ButtonState> {
+        let (state, opposite) = if self.pressed() {
+            (ButtonState::Pressed, ButtonState::Released)
+        } else {
+            (ButtonState::Released, ButtonState::Pressed)
+        };
+        match (self.just_pressed(), self.just_released()) {
+            (true, true) => vec![opposite, state],
+            (false, false) => vec![],
+            _ => vec![state],
+        }
+    }
+    fn apply(&self, button: &mut ButtonInput, buffer: Option<Mut<BufferedPress>>) {
+        let state = if self.pressed() {ButtonState::Pressed} else {ButtonState::Released};
+        let events = self.events();
+        // avoid false change detection
+        if !button.is(state) || button.events() != events {
+            button.restore(state, events);
+        }
+        if let Some(mut buffer) = buffer {
+            // avoid false change detection
+            if buffer.is_pending() != self.pending() {
+                buffer.set_pending(self.pending());
+            }
+        }
+    }
+}
+
+/// Outputs of [`PlayerInputs`] in one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub jump: ButtonFrame,
+    pub fire: ButtonFrame,
+    pub reload: ButtonFrame,
+    pub crouch: ButtonFrame,
+    pub sprint: ButtonFrame,
+    pub locomotion: Vec3,
+    /// Rotation of the character in the frame.
+    pub rotation: Quat,
+    pub head_attitude: Quat,
+}
+impl Default for InputFrame {
+    fn default() -> Self {
+        Self {
+            jump: ButtonFrame::default(),
+            fire: ButtonFrame::default(),
+            reload: ButtonFrame::default(),
+            crouch: ButtonFrame::default(),
+            sprint: ButtonFrame::default(),
+            locomotion: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            head_attitude: Quat::IDENTITY,
+        }
+    }
+}
+impl InputFrame {
+    /// Version of the encoding, increment on any change of the layout.
+    pub const VERSION: u8 = 3;
+
+    /// None if any of the outputs is missing.
+    pub fn capture(world: &World, inputs: &PlayerInputs) -> Option<Self> {
+        let button = |entity: Entity| world.get::<ButtonInput>(entity).map(|button| ButtonFrame::capture(button, world.get::<BufferedPress>(entity)));
+        Some(Self {
+            jump: button(inputs.jump)?,
+            fire: button(inputs.fire)?,
+            reload: button(inputs.reload)?,
+            crouch: button(inputs.crouch)?,
+            sprint: button(inputs.sprint)?,
+            locomotion: world.get::<PositionalInput>(inputs.locomotion)?.value,
+            rotation: world.get::<RotationalInput>(inputs.rotation)?.value,
+            head_attitude: world.get::<RotationalInput>(inputs.head_attitude)?.value,
+        })
+    }
+
+    /// Writes onto the outputs, skipping missing ones.
+    pub fn apply(&self, world: &mut World, inputs: &PlayerInputs) {
+        let mut buttons = world.query::<(&mut ButtonInput, Option<&mut BufferedPress>)>();
+        for (entity, frame) in [(inputs.jump, self.jump), (inputs.fire, self.fire), (inputs.reload, self.reload), (inputs.crouch, self.crouch), (inputs.sprint, self.sprint)] {
+            let Ok((mut button, buffer)) = buttons.get_mut(world, entity) else {
+                warn!("Entity not found");
+                continue;
+            };
+            frame.apply(&mut button, buffer);
+        }
+        if let Some(mut locomotion) = world.get_mut::<PositionalInput>(inputs.locomotion) {
+            // check real change for component change detection
+            if locomotion.value != self.locomotion {
+                locomotion.value = self.locomotion;
+            }
+        }
+        for (entity, value) in [(inputs.rotation, self.rotation), (inputs.head_attitude, self.head_attitude)] {
+            if let Some(mut rotation) = world.get_mut::<RotationalInput>(entity) {
+                // avoid false change detection
+                if rotation.value != value {
+                    rotation.value = value;
+                }
+            }
+        }
+    }
+
+    /// The version followed by the frame.
+    pub fn encode(&self) -> Result<Vec<u8>, InputFrameError> {
+        let mut bytes = vec![Self::VERSION];
+        bincode::serialize_into(&mut bytes, self).map_err(InputFrameError::Encoding)?;
+        Ok(bytes)
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self, InputFrameError> {
+        let Some((&version, body)) = bytes.split_first() else {
+            return Err(InputFrameError::Empty);
+        };
+        if version != Self::VERSION {
+            return Err(InputFrameError::UnsupportedVersion(version));
+        }
+        bincode::deserialize(body).map_err(InputFrameError::Encoding)
+    }
+}
+
+#[derive(Debug)]
+pub enum InputFrameError {
+    Empty,
+    UnsupportedVersion(u8),
+    Encoding(bincode::Error),
+}
+impl fmt::Display for InputFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty input frame"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported input frame version {}", version),
+            Self::Encoding(err) => write!(f, "{}", err),
+        }
+    }
+}
+impl std::error::Error for InputFrameError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_outputs(world: &mut World) -> PlayerInputs {
+        let mut button = || world.spawn(ButtonInput::default()).id();
+        let (jump, fire, reload, crouch, sprint) = (button(), button(), button(), button(), button());
+        world.entity_mut(fire).insert(BufferedPress::new(fire, 0.2));
+        PlayerInputs {
+            locomotion: world.spawn(PositionalInput::default()).id(),
+            rotation: world.spawn(RotationalInput::default()).id(),
+            head_attitude: world.spawn(RotationalInput::default()).id(),
+            jump, fire, reload, crouch, sprint,
+        }
+    }
+
+    fn frame() -> InputFrame {
+        InputFrame {
+            jump: ButtonFrame::new(true, true, false, false),
+            fire: ButtonFrame::new(false, true, true, true),
+            reload: ButtonFrame::new(false, false, true, false),
+            crouch: ButtonFrame::new(true, true, true, false),
+            locomotion: Vec3::new(0.5, 0.0, -1.0),
+            rotation: Quat::from_rotation_y(0.3),
+            head_attitude: Quat::from_rotation_x(-0.2),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let frame = frame();
+        let bytes = frame.encode().unwrap();
+        assert_eq!(bytes[0], InputFrame::VERSION);
+        assert_eq!(InputFrame::decode(&bytes).unwrap(), frame);
+    }
+
+    #[test]
+    fn decoding_rejects_other_versions() {
+        let mut bytes = frame().encode().unwrap();
+        bytes[0] = InputFrame::VERSION - 1;
+        assert!(matches!(InputFrame::decode(&bytes), Err(InputFrameError::UnsupportedVersion(version)) if version == InputFrame::VERSION - 1));
+        assert!(matches!(InputFrame::decode(&[]), Err(InputFrameError::Empty)));
+        assert!(matches!(InputFrame::decode(&[InputFrame::VERSION, 1]), Err(InputFrameError::Encoding(_))));
+    }
+
+    #[test]
+    fn applied_frames_capture_the_same() {
+        let mut world = World::new();
+        let inputs = spawn_outputs(&mut world);
+        let frame = frame();
+        frame.apply(&mut world, &inputs);
+
+        let button = |entity: Entity| world.get::<ButtonInput>(entity).unwrap();
+        assert_eq!(button(inputs.jump).events(), vec![ButtonState::Pressed]);
+        // tapped within the frame
+        assert_eq!(button(inputs.fire).events(), vec![ButtonState::Pressed, ButtonState::Released]);
+        assert!(button(inputs.fire).released());
+        assert!(world.get::<BufferedPress>(inputs.fire).unwrap().is_pending());
+        assert_eq!(button(inputs.reload).events(), vec![ButtonState::Released]);
+        // released and pressed again within the frame
+        assert_eq!(button(inputs.crouch).events(), vec![ButtonState::Released, ButtonState::Pressed]);
+        assert!(button(inputs.crouch).pressed());
+        assert!(button(inputs.sprint).events().is_empty());
+
+        assert_eq!(InputFrame::capture(&world, &inputs), Some(frame));
+    }
+
+    #[test]
+    fn capture_needs_every_output() {
+        let mut world = World::new();
+        let inputs = spawn_outputs(&mut world);
+        assert_eq!(InputFrame::capture(&world, &inputs), Some(InputFrame::default()));
+        world.despawn(inputs.sprint);
+        assert_eq!(InputFrame::capture(&world, &inputs), None);
+    }
+}
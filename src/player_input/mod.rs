@@ -0,0 +1,239 @@
+use bevy::{
+    prelude::*, ecs::system::EntityCommands,
+};
+use serde::{Serialize, Deserialize};
+use crate::cascade_input::{
+    order::{CascadeAppExt, CascadeNodeSet, CascadeNode, CascadeOrder},
+    button::{ButtonInput, Toggle, update_toggle_buttons},
+    timing::{Hold, Tap, DoubleTap, LongPress, update_hold_buttons, update_tap_buttons, update_double_tap_buttons, update_long_press_buttons},
+    chord::{Chord, Modifier, update_chord_buttons, update_modifier_buttons},
+    combo::{Combo, update_combo_buttons},
+    filter::apply_response_curves,
+    axis::{StickInput, StickButtons, update_four_button_axis, PositionalInput, EulerAngleInput, update_rotation_from_euler, RotationalInput, MappedEulerAngle},
+};
+
+use self::{
+    binding::BindingProfilePlugin,
+    virtual_gamepad::{VirtualGamepadPlugin, VirtualGamepad, VirtualGamepadInstance},
+};
+
+pub mod binding;
+pub mod virtual_gamepad;
+/// Snapshots of the outputs of the player, to encode and to apply back.
+pub mod frame;
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct DummyLabel;
+
+pub struct PlayerInputPlugin;
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins((BindingProfilePlugin, VirtualGamepadPlugin))
+            .add_cascade_node::<StickButtons, _>(CascadeNodeSet::Evaluate, update_four_button_axis)
+            .add_cascade_node::<Toggle<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_toggle_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<Hold<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_hold_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<Tap<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_tap_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<DoubleTap<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_double_tap_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<LongPress<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_long_press_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<Chord<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_chord_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<Modifier<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_modifier_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<Combo<AssetNodeLabel>, _>(CascadeNodeSet::Evaluate, update_combo_buttons::<AssetNodeLabel>)
+            .add_cascade_node::<MappedStick, _>(CascadeNodeSet::Evaluate, update_locomotion_from_stick)
+            .add_cascade_node::<ScaledByTime, _>(CascadeNodeSet::Evaluate, update_scaled_by_time)
+            .add_cascade_node::<MappedEulerAngle<DummyLabel>, _>(CascadeNodeSet::Evaluate, update_rotation_from_euler::<DummyLabel>)
+            // walk after shaping, on the same stick
+            .add_cascade_node::<WalkMode, _>(CascadeNodeSet::Filter, update_walking.after(apply_response_curves))
+            .add_cascade_node::<TargetRotation, _>(CascadeNodeSet::Propagate, update_rotation_from_stick)
+        ;
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerInputs {
+    pub locomotion: Entity,
+    pub rotation: Entity,
+    pub head_attitude: Entity,
+    pub jump: Entity,
+    pub fire: Entity,
+    pub reload: Entity,
+    pub crouch: Entity,
+    pub sprint: Entity,
+}
+/// Device layout of the virtual gamepad.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+pub enum PlayerInputLayout {
+    #[default]
+    KeyboardMouse,
+    Gamepad,
+}
+impl PlayerInputLayout {
+    /// Virtual gamepad asset of the layout.
+    pub fn asset_path(&self) -> &'static str {
+        match self {
+            Self::KeyboardMouse => "input/keyboard_mouse.gamepad.ron",
+            Self::Gamepad => "input/gamepad.gamepad.ron",
+        }
+    }
+}
+/// Spawns the outputs, and the graph behind them once the virtual gamepad is loaded.
+pub fn create_player_inputs<'w, 's, 'a, 'b>(commands: &'b mut EntityCommands<'w, 's, 'a>, gamepad: Handle<VirtualGamepad>) -> PlayerInputs {
+    let mut outputs = None;
+    commands.with_children(|builder| {
+        outputs = Some(PlayerInputs {
+            locomotion: builder.spawn(PositionalInput::default()).id(),
+            rotation: builder.spawn(RotationalInput::default()).id(),
+            head_attitude: builder.spawn(RotationalInput::default()).id(),
+            jump: builder.spawn(ButtonInput::default()).id(),
+            fire: builder.spawn(ButtonInput::default()).id(),
+            reload: builder.spawn(ButtonInput::default()).id(),
+            crouch: builder.spawn(ButtonInput::default()).id(),
+            sprint: builder.spawn(ButtonInput::default()).id(),
+        });
+    });
+    let outputs = outputs.unwrap();
+    commands.insert(VirtualGamepadInstance::new(gamepad, outputs));
+    outputs
+}
+
+
+/// Labels the generic nodes built from virtual gamepad assets.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+struct AssetNodeLabel;
+
+/// Scales the stick while walking, except while sprinting.
+/// The input knows nothing of stamina, so sprinting overrides walking even while the character is exhausted,
+/// where the character runs at the normal speed instead of sprinting.
+#[derive(Component)]
+struct WalkMode {
+    walking: Entity,
+    amp: f32,
+    sprint: Option<Entity>,
+}
+impl CascadeNode for WalkMode {
+    fn sources(&self) -> Vec<Entity> { [Some(self.walking), self.sprint].into_iter().flatten().collect() }
+}
+fn update_walking(
+    mut sticks: Query<(Entity, &mut StickInput, &WalkMode)>,
+    buttons: Query<&ButtonInput>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, walk_mode) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(walking) = buttons.get(walk_mode.walking) else {
+            warn!("Entity not found");
+            continue;
+        };
+        if walking.released() {continue;};
+        // sprinting overrides walking, which resumes on release
+        if let Some(sprint) = walk_mode.sprint {
+            let Ok(sprint) = buttons.get(sprint) else {
+                warn!("Entity not found");
+                continue;
+            };
+            if sprint.pressed() {continue;}
+        }
+        let value = stick.value * walk_mode.amp;
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+
+#[derive(Component)]
+struct  MappedStick {
+    stick: Entity,
+}
+impl CascadeNode for MappedStick {
+    fn sources(&self) -> Vec<Entity> { vec![self.stick] }
+}
+fn update_locomotion_from_stick(
+    mut locomotions: Query<(Entity, &mut PositionalInput, &MappedStick)>,
+    sticks: Query<&StickInput>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut locomotion, mapped_stick) in locomotions.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(stick) = sticks.get(mapped_stick.stick) else {
+            warn!("Entity not found");
+            continue;
+        };
+        let value = Vec3::new(stick.value.x, 0.0, -stick.value.y);
+        // check real change for component change detection
+        if locomotion.value != value {
+            locomotion.value = value;
+        }
+    }
+}
+
+/// Turns rate-like stick into per-frame delta like mouse motion.
+#[derive(Component)]
+struct ScaledByTime {
+    source: Entity,
+    scale: Vec2,
+}
+impl CascadeNode for ScaledByTime {
+    fn sources(&self) -> Vec<Entity> { vec![self.source] }
+}
+fn update_scaled_by_time(
+    mut sticks: Query<(Entity, &mut StickInput, &ScaledByTime)>,
+    sources: Query<&StickInput, Without<ScaledByTime>>,
+    time: Res<Time>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, mut stick, scaled) in sticks.iter_mut() {
+        if !order.is_current(entity) {continue;}
+        let Ok(source) = sources.get(scaled.source) else {
+            warn!("Entity not found");
+            continue;
+        };
+        let value = source.value * scaled.scale * time.delta_seconds();
+        // check real change for component change detection
+        if stick.value != value {
+            stick.value = value;
+        }
+    }
+}
+
+#[derive(Component)]
+struct  TargetRotation {    // attach this to stick
+    sensitivity: Vec2,
+    /// Lowest and highest pitch of the head, in radians.
+    pitch_range: (f32, f32),
+    rotation: Entity,
+    head_attitude: Entity,
+}
+impl CascadeNode for TargetRotation {
+    fn targets(&self) -> Vec<Entity> { vec![self.rotation, self.head_attitude] }
+}
+fn update_rotation_from_stick(
+    mut angles: Query<&mut EulerAngleInput>,
+    sticks: Query<(Entity, &StickInput, &TargetRotation)>,
+    order: Res<CascadeOrder>,
+) {
+    for (entity, stick, target) in sticks.iter() {
+        if !order.is_current(entity) {continue;}
+        let Ok(mut rotation) = angles.get_mut(target.rotation) else {
+            warn!("Entity not found");
+            continue;
+        };
+        let rotation_value = Vec3::new(0.0, -target.sensitivity.x * stick.value.x, 0.0);
+        // avoid false change detection
+        if rotation.value != rotation_value {
+            rotation.value = rotation_value;
+        }
+        let Ok(mut head_attitude) = angles.get_mut(target.head_attitude) else {
+            warn!("Entity not found");
+            continue;
+        };
+        let attitude_x = (head_attitude.value.x - target.sensitivity.y * stick.value.y).clamp(target.pitch_range.0, target.pitch_range.1);
+        // avoid false change detection
+        if head_attitude.value.x != attitude_x {
+            head_attitude.value.x = attitude_x;
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,571 @@
+//! Describes the input graph of the player as an asset.
+//!
+//! Nodes are named, and refer each other by the names.
+//! Outputs are spawned beforehand by [`create_player_inputs`](super::create_player_inputs) and kept through reloads,
+//! so the references to [`PlayerInputs`] stay valid while the graph behind them is rebuilt.
+
+use std::collections::BTreeMap;
+
+use bevy::{
+    prelude::*,
+    asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture},
+    reflect::{TypeUuid, TypePath},
+};
+use serde::Deserialize;
+
+use crate::{
+    global_settings::NamedInputContext,
+    cascade_input::{
+        context::InputContext,
+        button::{ButtonInput, MappedDeviceButton, DeviceButtonCode, Toggle},
+        timing::{Hold, Tap, DoubleTap, LongPress},
+        chord::{Chord, Modifier},
+        combo::{Combo, ComboStep},
+        buffer::BufferedPress,
+        convert::Ramp,
+        merge::{ButtonAny, StickSum, StickMaxMagnitude, RotationCompose},
+        filter::{ScaledDeadZone, ResponseCurve},
+        axis::{StickInput, StickButtons, MappedMouse, MappedWheel, MappedGamepadStick, MaxLength, DeadZone, PositionalInput, EulerAngleInput, RotationalInput, MappedEulerAngle},
+    },
+};
+
+use super::{
+    PlayerInputs, DummyLabel, AssetNodeLabel, WalkMode, MappedStick, TargetRotation, ScaledByTime,
+    binding::{BindingProfile, PlayerAction, BoundAction, BoundLook},
+};
+
+
+pub struct VirtualGamepadPlugin;
+impl Plugin for VirtualGamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<VirtualGamepad>()
+            .init_asset_loader::<VirtualGamepadLoader>()
+            .add_systems(PreUpdate, build_virtual_gamepads.before(crate::cascade_input::CascadeInputSet::Begin))
+        ;
+    }
+}
+
+
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "5d0b8a52-3f1e-4c8e-9a57-2f64c1d7e0b3"]
+pub struct VirtualGamepad {
+    pub nodes: BTreeMap<String, InputNode>,
+    pub outputs: VirtualGamepadOutputs,
+}
+
+/// Names of the nodes spawned on [`PlayerInputs`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct VirtualGamepadOutputs {
+    pub locomotion: String,
+    pub rotation: String,
+    pub head_attitude: String,
+    pub jump: String,
+    pub fire: String,
+    pub reload: String,
+    pub crouch: String,
+    /// Never pressed if none, such as in layouts made before sprinting.
+    #[serde(default)]
+    pub sprint: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum InputNode {
+    /// Button bound by [`BindingProfile`].
+    Action(PlayerAction),
+    /// Button fixed to the device.
+    Device(DeviceButtonCode),
+    Toggle(String),
+    Hold(String, f32),
+    /// Just pressed and released when the button is released within the seconds.
+    Tap(String, f32),
+    /// Pressed while the button is pressed again within the seconds.
+    DoubleTap(String, f32),
+    /// Just pressed and released once when the button is held for the seconds.
+    LongPress(String, f32),
+    /// Keeps a press of the button pending for the seconds, until a state machine consumes it.
+    Buffered(String, f32),
+    /// Toggle or hold by `hold_to_walk` of [`BindingProfile`].
+    Walk(String),
+    /// Pressed while any of the buttons is pressed.
+    Any(Vec<String>),
+    /// Pressed while all of the buttons are pressed.
+    Chord(Vec<String>),
+    /// Pressed while all of the buttons are pressed, in the order.
+    OrderedChord(Vec<String>),
+    /// The first button pressed while the second, the modifier, is held.
+    With(String, String),
+    /// The first button pressed while the second, the modifier, is not held.
+    Without(String, String),
+    /// Just pressed and released when the steps arrive in order within the seconds.
+    Combo(Vec<ComboStepNode>, f32),
+    Stick(StickNode),
+    EulerAngle,
+    Rotation(String),
+    /// Rotations composed in order.
+    Compose(Vec<String>),
+    Locomotion(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum ComboStepNode {
+    /// The button is just pressed.
+    Press(String),
+    /// The stick enters the direction.
+    Direction(String, Vec2),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StickNode {
+    pub source: StickSource,
+    #[serde(default)]
+    pub max_length: Option<f32>,
+    #[serde(default)]
+    pub dead_zone: Option<f32>,
+    #[serde(default)]
+    pub scaled_dead_zone: Option<ScaledDeadZone>,
+    #[serde(default)]
+    pub response_curve: Option<ResponseCurve>,
+    /// Name of the walking button and the amplitude while walking.
+    #[serde(default)]
+    pub walk_mode: Option<(String, f32)>,
+    /// Name of the sprint button, which overrides the walk mode while pressed, even while exhausted.
+    #[serde(default)]
+    pub sprint: Option<String>,
+    /// Names of the euler angles to rotate.
+    #[serde(default)]
+    pub target_rotation: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum StickSource {
+    Buttons {
+        negative_x: String,
+        positive_x: String,
+        negative_y: String,
+        positive_y: String,
+    },
+    /// Buttons moving the stick gradually, in units per second.
+    Ramp {
+        negative_x: String,
+        positive_x: String,
+        negative_y: String,
+        positive_y: String,
+        acceleration: f32,
+        deceleration: f32,
+    },
+    /// Mouse with the sensitivity of [`BindingProfile`].
+    Mouse,
+    /// Mouse wheel in lines, positive for up and right.
+    Wheel {
+        sensitivity: Vec2,
+    },
+    Gamepad {
+        x: GamepadAxisType,
+        y: GamepadAxisType,
+        sensitivity: Vec2,
+    },
+    /// Other stick scaled by the look speed of [`BindingProfile`] and the frame time.
+    ScaledByTime(String),
+    Sum(Vec<String>),
+    /// The longest of the sticks.
+    MaxMagnitude(Vec<String>),
+}
+
+impl VirtualGamepad {
+    /// Names referred from nodes and outputs, to validate before spawning.
+    fn references(&self) -> Vec<&String> {
+        let outputs = &self.outputs;
+        let mut references = vec![
+            &outputs.locomotion, &outputs.rotation, &outputs.head_attitude,
+            &outputs.jump, &outputs.fire, &outputs.reload, &outputs.crouch,
+        ];
+        references.extend(&outputs.sprint);
+        for node in self.nodes.values() {
+            match node {
+                InputNode::Toggle(source) | InputNode::Hold(source, _) | InputNode::Tap(source, _) | InputNode::DoubleTap(source, _) | InputNode::LongPress(source, _) | InputNode::Buffered(source, _) | InputNode::Walk(source) | InputNode::Rotation(source) | InputNode::Locomotion(source) => {
+                    references.push(source);
+                },
+                InputNode::Any(sources) | InputNode::Chord(sources) | InputNode::OrderedChord(sources) | InputNode::Compose(sources) => {
+                    references.extend(sources);
+                },
+                InputNode::With(source, modifier) | InputNode::Without(source, modifier) => {
+                    references.extend([source, modifier]);
+                },
+                InputNode::Combo(steps, _) => {
+                    references.extend(steps.iter().map(|step| match step {
+                        ComboStepNode::Press(source) | ComboStepNode::Direction(source, _) => source,
+                    }));
+                },
+                InputNode::Stick(stick) => {
+                    match &stick.source {
+                        StickSource::Buttons { negative_x, positive_x, negative_y, positive_y }
+                        | StickSource::Ramp { negative_x, positive_x, negative_y, positive_y, .. } => {
+                            references.extend([negative_x, positive_x, negative_y, positive_y]);
+                        },
+                        StickSource::ScaledByTime(source) => references.push(source),
+                        StickSource::Sum(sources) | StickSource::MaxMagnitude(sources) => references.extend(sources),
+                        StickSource::Mouse | StickSource::Wheel { .. } | StickSource::Gamepad { .. } => {},
+                    }
+                    if let Some((walking, _)) = &stick.walk_mode {
+                        references.push(walking);
+                    }
+                    if let Some(sprint) = &stick.sprint {
+                        references.push(sprint);
+                    }
+                    if let Some((rotation, head_attitude)) = &stick.target_rotation {
+                        references.extend([rotation, head_attitude]);
+                    }
+                },
+                InputNode::Action(_) | InputNode::Device(_) | InputNode::EulerAngle => {},
+            }
+        }
+        references
+    }
+    /// Checks the references and the kinds of outputs.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(missing) = self.references().into_iter().find(|name| !self.nodes.contains_key(*name)) {
+            return Err(format!("undefined node {:?}", missing));
+        }
+        let outputs = &self.outputs;
+        let is_button = |node: &InputNode| matches!(node, InputNode::Action(_) | InputNode::Device(_) | InputNode::Toggle(_) | InputNode::Hold(..) | InputNode::Tap(..) | InputNode::DoubleTap(..) | InputNode::LongPress(..) | InputNode::Buffered(..) | InputNode::Walk(_) | InputNode::Any(_) | InputNode::Chord(_) | InputNode::OrderedChord(_) | InputNode::With(..) | InputNode::Without(..) | InputNode::Combo(..));
+        let is_rotation = |node: &InputNode| matches!(node, InputNode::Rotation(_) | InputNode::Compose(_));
+        let is_locomotion = |node: &InputNode| matches!(node, InputNode::Locomotion(_));
+        let mut kinds: Vec<(&String, &dyn Fn(&InputNode) -> bool)> = vec![
+            (&outputs.locomotion, &is_locomotion), (&outputs.rotation, &is_rotation), (&outputs.head_attitude, &is_rotation),
+            (&outputs.jump, &is_button), (&outputs.fire, &is_button), (&outputs.reload, &is_button), (&outputs.crouch, &is_button),
+        ];
+        if let Some(sprint) = &outputs.sprint {
+            kinds.push((sprint, &is_button));
+        }
+        for &(name, is_kind) in &kinds {
+            if !is_kind(&self.nodes[name]) {
+                return Err(format!("output {:?} is the wrong kind of node", name));
+            }
+        }
+        let mut names: Vec<&String> = kinds.iter().map(|(name, _)| *name).collect();
+        names.sort();
+        names.dedup();
+        if names.len() != kinds.len() {
+            return Err("outputs share a node".to_string());
+        }
+        Ok(())
+    }
+    fn output(&self, outputs: &PlayerInputs, name: &str) -> Option<Entity> {
+        let names = &self.outputs;
+        [
+            (&names.locomotion, outputs.locomotion), (&names.rotation, outputs.rotation), (&names.head_attitude, outputs.head_attitude),
+            (&names.jump, outputs.jump), (&names.fire, outputs.fire), (&names.reload, outputs.reload), (&names.crouch, outputs.crouch),
+        ].into_iter()
+            .chain(names.sprint.as_ref().map(|sprint| (sprint, outputs.sprint)))
+            .find(|(output, _)| *output == name).map(|(_, entity)| entity)
+    }
+}
+
+
+#[derive(Default)]
+pub struct VirtualGamepadLoader;
+impl AssetLoader for VirtualGamepadLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let gamepad = ron::de::from_bytes::<VirtualGamepad>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(gamepad));
+            Ok(())
+        })
+    }
+    fn extensions(&self) -> &[&str] {
+        &["gamepad.ron"]
+    }
+}
+
+
+/// Spawns the graph of the asset as children of this entity, and rebuilds it on reload.
+#[derive(Component)]
+pub struct VirtualGamepadInstance {
+    pub handle: Handle<VirtualGamepad>,
+    pub outputs: PlayerInputs,
+    nodes: Vec<Entity>,
+    built: bool,
+}
+impl VirtualGamepadInstance {
+    pub fn new(handle: Handle<VirtualGamepad>, outputs: PlayerInputs) -> Self {
+        Self { handle, outputs, nodes: Vec::default(), built: false }
+    }
+}
+
+/// Components which nodes may insert into outputs, removed before rebuilding.
+type OutputNodeComponents = (
+    Name, InputContext, BoundAction, MappedDeviceButton, BufferedPress,
+    (Toggle<AssetNodeLabel>, Hold<AssetNodeLabel>, Tap<AssetNodeLabel>, DoubleTap<AssetNodeLabel>, LongPress<AssetNodeLabel>),
+    (Chord<AssetNodeLabel>, Modifier<AssetNodeLabel>, Combo<AssetNodeLabel>),
+    MappedStick, MappedEulerAngle<DummyLabel>, ButtonAny, RotationCompose,
+);
+
+fn build_virtual_gamepads(
+    mut commands: Commands,
+    mut instances: Query<(Entity, &mut VirtualGamepadInstance)>,
+    mut asset_events: EventReader<AssetEvent<VirtualGamepad>>,
+    gamepads: Res<Assets<VirtualGamepad>>,
+    profile: Res<BindingProfile>,
+) {
+    for event in asset_events.iter() {
+        let AssetEvent::Modified { handle } = event else {continue;};
+        for (_, mut instance) in instances.iter_mut() {
+            if instance.handle == *handle {
+                instance.built = false;
+            }
+        }
+    }
+    for (owner, mut instance) in instances.iter_mut() {
+        if instance.built {continue;}
+        let Some(gamepad) = gamepads.get(&instance.handle) else {continue;};   // not loaded yet
+        instance.built = true;
+        if let Err(error) = gamepad.validate() {
+            error!("Invalid virtual gamepad: {}, keeping the previous graph.", error);
+            continue;
+        }
+
+        // clear the previous graph
+        for node in instance.nodes.drain(..) {
+            commands.entity(node).despawn_recursive();
+        }
+        let outputs = instance.outputs;
+        for output in [outputs.jump, outputs.fire, outputs.reload, outputs.crouch, outputs.sprint] {
+            commands.entity(output).remove::<OutputNodeComponents>().insert(ButtonInput::default());
+        }
+        commands.entity(outputs.locomotion).remove::<OutputNodeComponents>().insert(PositionalInput::default());
+        for output in [outputs.rotation, outputs.head_attitude] {
+            commands.entity(output).remove::<OutputNodeComponents>().insert(RotationalInput::default());
+        }
+
+        // allocate entities first, since nodes may refer forward
+        let mut entities: BTreeMap<&str, Entity> = BTreeMap::new();
+        for name in gamepad.nodes.keys() {
+            let entity = match gamepad.output(&outputs, name) {
+                Some(output) => output,
+                None => {
+                    let node = commands.spawn_empty().set_parent(owner).id();
+                    instance.nodes.push(node);
+                    node
+                },
+            };
+            entities.insert(name.as_str(), entity);
+        }
+        let entity_of = |name: &String| entities[name.as_str()];
+
+        for (name, node) in gamepad.nodes.iter() {
+            let mut builder = commands.entity(entity_of(name));
+            builder.insert(Name::new(name.clone()));
+            match node {
+                InputNode::Action(action) => {
+                    builder.insert((
+                        NamedInputContext::GAMEPLAY,
+                        ButtonInput::default(),
+                        BoundAction(*action),
+                    ));
+                    if let Some(code) = profile.code(*action) {
+                        builder.insert(MappedDeviceButton::new(code));
+                    }
+                },
+                InputNode::Device(code) => {
+                    builder.insert((
+                        NamedInputContext::GAMEPLAY,
+                        ButtonInput::default(),
+                        MappedDeviceButton::new(*code),
+                    ));
+                },
+                InputNode::Toggle(source) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Toggle::<AssetNodeLabel>::new(entity_of(source)),
+                    ));
+                },
+                InputNode::Hold(source, min_duration) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Hold::<AssetNodeLabel>::new(entity_of(source), *min_duration),
+                    ));
+                },
+                InputNode::Tap(source, max_duration) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Tap::<AssetNodeLabel>::new(entity_of(source), *max_duration),
+                    ));
+                },
+                InputNode::DoubleTap(source, window) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        DoubleTap::<AssetNodeLabel>::new(entity_of(source), *window),
+                    ));
+                },
+                InputNode::LongPress(source, duration) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        LongPress::<AssetNodeLabel>::new(entity_of(source), *duration),
+                    ));
+                },
+                InputNode::Buffered(source, window) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        BufferedPress::new(entity_of(source), *window),
+                    ));
+                },
+                InputNode::Walk(source) => {
+                    builder.insert(ButtonInput::default());
+                    if profile.hold_to_walk {
+                        builder.insert(Hold::<AssetNodeLabel>::new(entity_of(source), 0.0));
+                    } else {
+                        builder.insert(Toggle::<AssetNodeLabel>::new(entity_of(source)));
+                    }
+                },
+                InputNode::Any(sources) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        ButtonAny { sources: sources.iter().map(entity_of).collect() },
+                    ));
+                },
+                InputNode::Chord(sources) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Chord::<AssetNodeLabel>::new(sources.iter().map(entity_of).collect()),
+                    ));
+                },
+                InputNode::OrderedChord(sources) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Chord::<AssetNodeLabel>::ordered(sources.iter().map(entity_of).collect()),
+                    ));
+                },
+                InputNode::With(source, modifier) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Modifier::<AssetNodeLabel>::with(entity_of(source), entity_of(modifier)),
+                    ));
+                },
+                InputNode::Without(source, modifier) => {
+                    builder.insert((
+                        ButtonInput::default(),
+                        Modifier::<AssetNodeLabel>::without(entity_of(source), entity_of(modifier)),
+                    ));
+                },
+                InputNode::Combo(steps, window) => {
+                    let steps = steps.iter().map(|step| match step {
+                        ComboStepNode::Press(source) => ComboStep::Press(entity_of(source)),
+                        ComboStepNode::Direction(source, direction) => ComboStep::direction(entity_of(source), *direction),
+                    }).collect();
+                    builder.insert((
+                        ButtonInput::default(),
+                        Combo::<AssetNodeLabel>::new(steps, *window),
+                    ));
+                },
+                InputNode::Stick(stick) => {
+                    builder.insert(StickInput::default());
+                    match &stick.source {
+                        StickSource::Buttons { negative_x, positive_x, negative_y, positive_y } => {
+                            builder.insert(StickButtons {
+                                negative_x: entity_of(negative_x),
+                                positive_x: entity_of(positive_x),
+                                negative_y: entity_of(negative_y),
+                                positive_y: entity_of(positive_y),
+                            });
+                        },
+                        StickSource::Ramp { negative_x, positive_x, negative_y, positive_y, acceleration, deceleration } => {
+                            builder.insert(Ramp {
+                                buttons: StickButtons {
+                                    negative_x: entity_of(negative_x),
+                                    positive_x: entity_of(positive_x),
+                                    negative_y: entity_of(negative_y),
+                                    positive_y: entity_of(positive_y),
+                                },
+                                acceleration: *acceleration,
+                                deceleration: *deceleration,
+                            });
+                        },
+                        StickSource::Mouse => {
+                            let mut mouse = MappedMouse::new(Vec2::ONE);
+                            profile.mouse_look.apply(&mut mouse);
+                            builder.insert((
+                                NamedInputContext::GAMEPLAY,
+                                mouse,
+                                BoundLook,
+                            ));
+                        },
+                        StickSource::Wheel { sensitivity } => {
+                            builder.insert((
+                                NamedInputContext::GAMEPLAY,
+                                MappedWheel::new(*sensitivity),
+                            ));
+                        },
+                        StickSource::Gamepad { x, y, sensitivity } => {
+                            builder.insert((
+                                NamedInputContext::GAMEPLAY,
+                                MappedGamepadStick::new(*x, *y, *sensitivity),
+                            ));
+                        },
+                        StickSource::Sum(sources) => {
+                            builder.insert(StickSum { sources: sources.iter().map(entity_of).collect() });
+                        },
+                        StickSource::MaxMagnitude(sources) => {
+                            builder.insert(StickMaxMagnitude { sources: sources.iter().map(entity_of).collect() });
+                        },
+                        StickSource::ScaledByTime(source) => {
+                            builder.insert((
+                                ScaledByTime { source: entity_of(source), scale: profile.gamepad_look_speed },
+                                BoundLook,
+                            ));
+                        },
+                    }
+                    if let Some(value) = stick.max_length {
+                        builder.insert(MaxLength { value });
+                    }
+                    if let Some(value) = stick.dead_zone {
+                        builder.insert(DeadZone { value });
+                    }
+                    if let Some(dead_zone) = stick.scaled_dead_zone {
+                        builder.insert(dead_zone);
+                    }
+                    if let Some(curve) = &stick.response_curve {
+                        builder.insert(curve.clone());
+                    }
+                    if let Some((walking, amp)) = &stick.walk_mode {
+                        builder.insert(WalkMode { walking: entity_of(walking), amp: *amp, sprint: stick.sprint.as_ref().map(|sprint| entity_of(sprint)) });
+                    }
+                    if let Some((rotation, head_attitude)) = &stick.target_rotation {
+                        builder.insert(TargetRotation {
+                            sensitivity: Vec2::ONE,
+                            pitch_range: profile.pitch_range_radians(),
+                            rotation: entity_of(rotation),
+                            head_attitude: entity_of(head_attitude),
+                        });
+                    }
+                },
+                InputNode::EulerAngle => {
+                    builder.insert(EulerAngleInput { value: Vec3::ZERO });
+                },
+                InputNode::Rotation(source) => {
+                    builder.insert((
+                        RotationalInput::default(),
+                        MappedEulerAngle::<DummyLabel>::new(entity_of(source)),
+                    ));
+                },
+                InputNode::Compose(sources) => {
+                    builder.insert((
+                        RotationalInput::default(),
+                        RotationCompose { sources: sources.iter().map(entity_of).collect() },
+                    ));
+                },
+                InputNode::Locomotion(source) => {
+                    builder.insert((
+                        PositionalInput::default(),
+                        MappedStick { stick: entity_of(source) },
+                    ));
+                },
+            }
+        }
+        info!("Virtual gamepad built.");
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use seldom_state::prelude::*;
 
-use crate::{cascade_input::button::ButtonJustPressedTrigger, util::state_machine::{insert_while_state, Timeout}};
+use crate::{cascade_input::{button::ButtonJustPressedTrigger, buffer::{BufferedPressTrigger, consume_buffered_press}}, util::state_machine::{insert_while_state, Timeout}};
 
 use super::Magazine;
 
@@ -55,15 +55,17 @@ impl BoolTrigger for ReloadableTrigger {
 
 pub struct SemiAutoStateMachine;
 impl SemiAutoStateMachine {
+    /// Fires also by a click buffered during the cooldown, if `fire_button` has `BufferedPress`.
     pub fn default_machine (fire_button: Entity, reload_button: Entity, fire_rate: f32, reload_time: f32) -> StateMachine {
         let machine = StateMachine::default()
             .trans::<Ready>(EmptyAmmoTrigger, Empty)
-            .trans::<Ready>((ButtonJustPressedTrigger { button: fire_button }).and(EmptyAmmoTrigger.not()), Fire)
+            .trans::<Ready>((BufferedPressTrigger { button: fire_button }).and(EmptyAmmoTrigger.not()), Fire)
             .trans::<Ready>((ButtonJustPressedTrigger { button: reload_button }).and(ReloadableTrigger), Reload)
             .trans::<Empty>((ButtonJustPressedTrigger { button: reload_button }).and(ReloadableTrigger), Reload)
             // .trans::<Empty>(QueryFilterTrigger<With<AutoEmergencyReload>> + ReloadableTrigger, Reload)
             // .trans::<Empty>(fire_button + QueryFilterTrigger<With<FireToEmergencyReload>> + ReloadableTrigger, Reload)
             .trans::<Fire>(DoneTrigger::Success, Ready)
+            .on_enter::<Fire>(consume_buffered_press(fire_button))
             .trans::<Reload>(DoneTrigger::Success, Ready)
             // .trans::<Reload>(QueryFilterTrigger<With<Canceled>>, Ready
             .set_trans_logging(true)
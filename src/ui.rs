@@ -7,6 +7,8 @@ use bevy::{
 use crate::Player;
 use crate::global_settings::CameraOrder;
 use crate::projectile_spawner::Magazine;
+use crate::character_control::stamina::Stamina;
+use crate::cascade_input::debug::CascadeDebugPanel;
 
 
 pub struct GameUiPlugin;
@@ -14,7 +16,11 @@ impl Plugin for GameUiPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_systems(Startup, spawn_ui)
-            .add_systems(PostUpdate, (update_magazine_ui, ))
+            .add_systems(PostUpdate, (
+                update_magazine_ui,
+                update_stamina_ui,
+                update_input_debug_ui.run_if(resource_exists::<CascadeDebugPanel>()),
+            ))
         ;
     }
 }
@@ -62,6 +68,51 @@ pub fn spawn_ui (
         }),
         UiMagazine
     ));
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(25.0),
+                left: Val::Px(25.0),
+                width: Val::Px(200.0),
+                height: Val::Px(8.0),
+                ..default()
+            },
+            background_color: Color::rgba(1.0, 1.0, 1.0, 0.2).into(),
+            ..default()
+        })
+        .with_children(|gauge| {
+            gauge.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: Color::WHITE.into(),
+                    ..default()
+                },
+                UiStamina
+            ));
+        });
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            ..TextBundle::from_sections([
+                TextSection::from_style(TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                })
+                ]).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                ..default()
+            })
+        },
+        UiInputDebug
+    ));
 }
 
 #[derive(Component, Debug, Clone, Copy)]
@@ -90,3 +141,41 @@ fn update_magazine_ui (
         text.sections[0].value = format!("{}", magazine.ammo_count);
     }
 }
+
+#[derive(Component, Debug, Clone, Copy)]
+struct UiStamina;
+fn update_stamina_ui (
+    player_query: Query<&Stamina, With<Player>>,
+    mut gauges: Query<(&mut Style, &mut BackgroundColor), With<UiStamina>>,
+) {
+    let Ok(stamina) = player_query.get_single() else {
+        return;
+    };
+    let width = Val::Percent(100.0 * stamina.fraction());
+    // tinted until recovered from exhaustion
+    let color = if stamina.is_exhausted() {Color::rgb(0.8, 0.3, 0.2)} else {Color::WHITE};
+    for (mut style, mut background) in gauges.iter_mut() {
+        // avoid false change detection
+        if style.width != width {
+            style.width = width;
+        }
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+struct UiInputDebug;
+fn update_input_debug_ui (
+    panel: Res<CascadeDebugPanel>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<UiInputDebug>>,
+) {
+    if !panel.is_changed() {
+        return;
+    }
+    for (mut text, mut visibility) in texts.iter_mut() {
+        *visibility = if panel.visible {Visibility::Inherited} else {Visibility::Hidden};
+        text.sections[0].value = panel.text.clone();
+    }
+}